@@ -0,0 +1,112 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    distributed::{run_worker, Coordinator, Partition},
+    hittable::{Bvh, HittableList, Quad, Sphere},
+    material::Material,
+    math::{prelude::*, Point3, Vector3},
+    texture::PerlinTexture,
+    Color,
+};
+use std::{
+    env,
+    process::{Child, Command},
+    sync::Arc,
+};
+
+const WORKERS: usize = 4;
+
+// Run without arguments to render on worker processes of this example started on the same host.
+// `distributed worker ADDR` runs a worker only, e.g. on another host.
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if let [_, mode, addr] = args.as_slice() {
+        assert_eq!(mode, "worker");
+        run_worker(addr.as_str()).unwrap();
+        return;
+    }
+
+    let mut world = HittableList::new();
+
+    let white = Material::lambertian(Color::new(0.73, 0.73, 0.73).into());
+    let light = Material::diffuse_light(Color::new(15., 15., 15.).into());
+    let marble = Material::lambertian(Arc::new(PerlinTexture::new(0.02)));
+
+    world.push(Quad::new(
+        Point3::new(555., 0., 0.),
+        Vector3::new(0., 555., 0.),
+        Vector3::new(0., 0., 555.),
+        Material::lambertian(Color::new(0.12, 0.45, 0.15).into()),
+    ));
+    world.push(Quad::new(
+        Point3::origin(),
+        Vector3::new(0., 555., 0.),
+        Vector3::new(0., 0., 555.),
+        Material::lambertian(Color::new(0.65, 0.05, 0.05).into()),
+    ));
+    world.push(Quad::new(
+        Point3::new(343., 554., 332.),
+        Vector3::new(-130., 0., 0.),
+        Vector3::new(0., 0., -105.),
+        light,
+    ));
+    world.push(Quad::new(
+        Point3::origin(),
+        Vector3::new(555., 0., 0.),
+        Vector3::new(0., 0., 555.),
+        white.clone(),
+    ));
+    world.push(Quad::new(
+        Point3::from([555.; 3]),
+        Vector3::new(-555., 0., 0.),
+        Vector3::new(0., 0., -555.),
+        white.clone(),
+    ));
+    world.push(Quad::new(
+        Point3::new(0., 0., 555.),
+        Vector3::new(555., 0., 0.),
+        Vector3::new(0., 555., 0.),
+        white,
+    ));
+
+    world.push(Sphere::new(Point3::new(190., 90., 190.), 90., marble));
+    world.push(Sphere::new(
+        Point3::new(370., 90., 370.),
+        90.,
+        Material::dielectric(1.5),
+    ));
+
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_width(600)
+        .image_height(600)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .background(Color::from([0.; 3]))
+        .vfov(40.)
+        .lookfrom(Point3::new(278., 278., -800.))
+        .lookat(Point3::new(278., 278., 0.));
+
+    let world = Bvh::from_list(&mut world);
+
+    let coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+    let addr = coordinator.local_addr().unwrap().to_string();
+    let exe = env::current_exe().unwrap();
+    let mut workers: Vec<Child> = (0..WORKERS)
+        .map(|_| Command::new(&exe).args(["worker", &addr]).spawn().unwrap())
+        .collect();
+
+    let (film, stats) = coordinator
+        .render(&camera, &world, WORKERS, Partition::Tiles(64))
+        .unwrap();
+    for worker in &mut workers {
+        worker.wait().unwrap();
+    }
+
+    println!("{}", stats);
+    film.save(
+        "output/distributed.png",
+        &camera.post_effects,
+        &camera.tone_mapping,
+    )
+    .unwrap();
+}
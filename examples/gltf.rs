@@ -0,0 +1,27 @@
+use path_tracer::{hittable::Bvh, loader::GltfLoader, Color};
+
+fn main() {
+    let mut scene = GltfLoader::new()
+        .light_radius(0.1)
+        .load("models/cubes.gltf")
+        .unwrap();
+    for warning in &scene.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let camera = scene
+        .cameras
+        .first()
+        .expect("the scene has a camera")
+        .clone()
+        .image_width(600)
+        .image_height(400)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .background(Color::new(0.1, 0.12, 0.15))
+        .build();
+
+    let world = Bvh::from_list(&mut scene.world);
+
+    camera.render(&world, "output/gltf.png").unwrap();
+}
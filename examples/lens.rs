@@ -0,0 +1,116 @@
+use path_tracer::{
+    camera::{CameraBuilder, LensElement, LensSystem, Projection},
+    hittable::{Bvh, HittableList, Quad, Transform},
+    material::Material,
+    math::{prelude::*, Deg, Point3, Quaternion, Vector3},
+    Color,
+};
+use std::sync::Arc;
+
+// Double Gauss 50mm f/2 (US patent 2,673,491), in millimeters: radius, thickness, ior, aperture.
+const DOUBLE_GAUSS: [[f64; 4]; 11] = [
+    [29.475, 3.76, 1.67, 25.2],
+    [84.83, 0.12, 1., 25.2],
+    [19.275, 4.025, 1.67, 23.],
+    [40.77, 3.275, 1.699, 23.],
+    [12.75, 5.705, 1., 18.],
+    [0., 4.5, 0., 17.1],
+    [-14.495, 1.18, 1.603, 17.],
+    [40.77, 6.065, 1.658, 20.],
+    [-20.385, 0.19, 1., 20.],
+    [437.065, 3.22, 1.717, 20.],
+    [-39.73, 0., 1., 20.],
+];
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let red = Material::lambertian(Color::new(0.65, 0.05, 0.05).into());
+    let white = Material::lambertian(Color::new(0.73, 0.73, 0.73).into());
+    let green = Material::lambertian(Color::new(0.12, 0.45, 0.15).into());
+    let light = Material::diffuse_light(Color::new(15., 15., 15.).into());
+
+    world.push(Quad::new(
+        Point3::new(555., 0., 0.),
+        Vector3::new(0., 555., 0.),
+        Vector3::new(0., 0., 555.),
+        green,
+    ));
+    world.push(Quad::new(
+        Point3::origin(),
+        Vector3::new(0., 555., 0.),
+        Vector3::new(0., 0., 555.),
+        red,
+    ));
+    world.push(Quad::new(
+        Point3::new(343., 554., 332.),
+        Vector3::new(-130., 0., 0.),
+        Vector3::new(0., 0., -105.),
+        light,
+    ));
+    world.push(Quad::new(
+        Point3::origin(),
+        Vector3::new(555., 0., 0.),
+        Vector3::new(0., 0., 555.),
+        white.clone(),
+    ));
+    world.push(Quad::new(
+        Point3::from([555.; 3]),
+        Vector3::new(-555., 0., 0.),
+        Vector3::new(0., 0., -555.),
+        white.clone(),
+    ));
+    world.push(Quad::new(
+        Point3::new(0., 0., 555.),
+        Vector3::new(555., 0., 0.),
+        Vector3::new(0., 555., 0.),
+        white.clone(),
+    ));
+
+    world.push(Transform::new(
+        Quad::cuboid(
+            Point3::new(0., 0., 0.),
+            Point3::new(165., 330., 165.),
+            white.clone(),
+        ),
+        Vector3::new(265., 0., 295.),
+        Quaternion::from_angle_y(Deg(15.)),
+    ));
+    world.push(Transform::new(
+        Quad::cuboid(
+            Point3::new(0., 0., 0.),
+            Point3::new(165., 165., 165.),
+            white,
+        ),
+        Vector3::new(130., 0., 65.),
+        Quaternion::from_angle_y(Deg(-18.)),
+    ));
+
+    let elements = DOUBLE_GAUSS
+        .iter()
+        .map(|&[radius, thickness, ior, aperture]| LensElement {
+            radius,
+            thickness,
+            ior,
+            aperture,
+        })
+        .collect();
+    // The Cornell box is modelled in millimeters, like the prescription. 43.27mm is the diagonal
+    // of a full-frame sensor.
+    let lens = LensSystem::new(elements, 43.27, 1.);
+
+    let camera = CameraBuilder::default()
+        .image_width(600)
+        .image_height(400)
+        .samples_per_pixel(1000)
+        .max_depth(50)
+        .background(Color::from([0.; 3]))
+        .projection(Projection::Realistic(Arc::new(lens)))
+        .lookfrom(Point3::new(278., 278., -800.))
+        .lookat(Point3::new(278., 278., 0.))
+        .build();
+
+    let world = Bvh::from_list(&mut world);
+
+    camera.render(&world, "output/lens.png").unwrap();
+}
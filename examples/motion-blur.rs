@@ -0,0 +1,53 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    hittable::{Bvh, HittableList, Quad, Sphere, Transform},
+    material::Material,
+    math::{prelude::*, Deg, Point3, Quaternion, Vector3},
+    Color,
+};
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let ground = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+    world.push(Sphere::new(Point3::new(0., -1000., 0.), 1000., ground));
+
+    let red = Material::lambertian(Color::new(0.7, 0.1, 0.1).into());
+    world.push(Sphere::moving(
+        Point3::new(-2., 0.5, 0.),
+        Point3::new(-2., 1., 0.),
+        0.5,
+        red,
+    ));
+
+    let blue = Material::lambertian(Color::new(0.1, 0.2, 0.6).into());
+    world.push(Sphere::moving(
+        Point3::new(-0.5, 0.5, 0.5),
+        Point3::new(0.5, 0.5, 0.5),
+        0.5,
+        blue,
+    ));
+
+    let gold = Material::metal(Color::new(0.8, 0.6, 0.2), 0.1);
+    world.push(Transform::moving(
+        Quad::cuboid(Point3::new(-0.5, 0., -0.5), Point3::new(0.5, 1., 0.5), gold),
+        (Vector3::new(2., 0., 0.), Quaternion::one()),
+        (Vector3::new(2., 0., 0.), Quaternion::from_angle_y(Deg(30.))),
+    ));
+
+    let camera = CameraBuilder::default()
+        .image_width(800)
+        .image_height(450)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .vfov(30.)
+        .lookfrom(Point3::new(0., 3., 9.))
+        .lookat(Point3::new(0., 0.5, 0.))
+        .shutter_open(0.)
+        .shutter_close(1.)
+        .build();
+
+    let world = Bvh::from_list(&mut world);
+
+    camera.render(&world, "output/motion-blur.png").unwrap();
+}
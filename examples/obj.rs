@@ -0,0 +1,42 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    hittable::{Bvh, HittableList, Sphere},
+    loader::ObjLoader,
+    material::Material,
+    math::Point3,
+    Color,
+};
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let house = ObjLoader::new(Material::lambertian(Color::new(0.5, 0.5, 0.5).into()))
+        .material(
+            "wall",
+            Material::lambertian(Color::new(0.8, 0.75, 0.6).into()),
+        )
+        .material("roof", Material::metal(Color::new(0.6, 0.15, 0.1), 0.3))
+        .load("models/house.obj")
+        .unwrap();
+    world.push(house);
+
+    world.push(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Material::lambertian(Color::new(0.3, 0.5, 0.2).into()),
+    ));
+
+    let camera = CameraBuilder::default()
+        .image_width(600)
+        .image_height(400)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(35.)
+        .lookfrom(Point3::new(-5., 3., -4.))
+        .lookat(Point3::new(1., 1., 1.5))
+        .build();
+
+    let world = Bvh::from_list(&mut world);
+
+    camera.render(&world, "output/obj.png").unwrap();
+}
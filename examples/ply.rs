@@ -0,0 +1,44 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    hittable::{Bvh, HittableList, Sphere, Transform},
+    loader::PlyLoader,
+    material::Material,
+    math::{Point3, Quaternion, Vector3},
+    texture::VertexColorTexture,
+    Color,
+};
+use std::sync::Arc;
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let vertex_colors = Arc::new(VertexColorTexture::new(Color::new(0.5, 0.5, 0.5)));
+    let sphere = PlyLoader::new(Material::lambertian(vertex_colors))
+        .load("models/icosphere.ply")
+        .unwrap();
+    world.push(Transform::new(
+        sphere,
+        Vector3::new(0., 1., 0.),
+        Quaternion::new(1., 0., 0., 0.),
+    ));
+
+    world.push(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Material::lambertian(Color::new(0.5, 0.5, 0.5).into()),
+    ));
+
+    let camera = CameraBuilder::default()
+        .image_width(600)
+        .image_height(400)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(30.)
+        .lookfrom(Point3::new(0., 3., 6.))
+        .lookat(Point3::new(0., 1., 0.))
+        .build();
+
+    let world = Bvh::from_list(&mut world);
+
+    camera.render(&world, "output/ply.png").unwrap();
+}
@@ -0,0 +1,52 @@
+use path_tracer::{
+    camera::{CameraBuilder, RenderOptions},
+    hittable::{HittableList, Sphere},
+    material::Material,
+    math::{prelude::*, Point3},
+    preview::PreviewServer,
+    progress::IndicatifProgress,
+    texture::PerlinTexture,
+};
+use std::{io, sync::Arc};
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let pertex = Arc::new(PerlinTexture::new(4.));
+
+    world.push(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Material::lambertian(pertex.clone()),
+    ));
+    world.push(Sphere::new(
+        Point3::new(0., 2., 0.),
+        2.,
+        Material::lambertian(pertex),
+    ));
+
+    let camera = CameraBuilder::default()
+        .image_width(800)
+        .image_height(450)
+        .samples_per_pixel(500)
+        .samples_per_pass(8)
+        .max_depth(50)
+        .vfov(20.)
+        .lookfrom(Point3::new(13., 2., 3.))
+        .lookat(Point3::origin())
+        .build();
+
+    let server = PreviewServer::bind("127.0.0.1:8000")
+        .unwrap()
+        .forward(IndicatifProgress::new());
+    println!("Preview at http://{}/", server.local_addr());
+
+    let mut options = RenderOptions::default();
+    options.progress(server);
+    camera
+        .render_with(&world, "output/preview.png", &options)
+        .unwrap();
+
+    println!("Press Enter to stop serving the preview.");
+    io::stdin().read_line(&mut String::new()).unwrap();
+}
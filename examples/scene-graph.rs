@@ -0,0 +1,83 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    hittable::{Quad, Sphere},
+    material::Material,
+    math::{Deg, Matrix4, Point3, Vector3},
+    scene::SceneGraph,
+    Color,
+};
+
+fn main() {
+    let mut scene = SceneGraph::new();
+
+    let ground = scene.add("ground", None);
+    scene.node_mut(ground).object(Sphere::new(
+        Point3::new(0., -1000., 0.),
+        1000.,
+        Material::lambertian(Color::new(0.5, 0.5, 0.5).into()),
+    ));
+
+    // The parts of the table are placed relative to it, so moving the table moves them all.
+    let table = scene.add("table", None);
+    scene
+        .node_mut(table)
+        .material(Material::lambertian(Color::new(0.45, 0.3, 0.15).into()));
+
+    let unit_box = Quad::cuboid(
+        Point3::new(-0.5, -0.5, -0.5),
+        Point3::new(0.5, 0.5, 0.5),
+        Material::lambertian(Color::new(0.73, 0.73, 0.73).into()),
+    );
+    let top = scene.add("top", Some(table));
+    scene.node_mut(top).object(unit_box.clone()).transform(
+        Matrix4::from_translation(Vector3::new(0., 1., 0.))
+            * Matrix4::from_nonuniform_scale(2., 0.1, 1.2),
+    );
+    for (i, (x, z)) in [(-0.9, -0.5), (0.9, -0.5), (-0.9, 0.5), (0.9, 0.5)]
+        .into_iter()
+        .enumerate()
+    {
+        let leg = scene.add(&format!("leg {}", i), Some(table));
+        scene.node_mut(leg).object(unit_box.clone()).transform(
+            Matrix4::from_translation(Vector3::new(x, 0.475, z))
+                * Matrix4::from_nonuniform_scale(0.1, 0.95, 0.1),
+        );
+    }
+
+    let vase = scene.add("vase", Some(table));
+    scene
+        .node_mut(vase)
+        .object(Sphere::new(
+            Point3::new(0., 0., 0.),
+            0.25,
+            Material::lambertian(Color::new(0.2, 0.3, 0.8).into()),
+        ))
+        .transform(
+            Matrix4::from_translation(Vector3::new(0.4, 1.3, 0.))
+                * Matrix4::from_nonuniform_scale(1., 1.6, 1.),
+        );
+
+    // Edit the scene by name: turn the table, make the vase glass and hide a leg.
+    let table = scene.find("table").unwrap();
+    scene
+        .node_mut(table)
+        .transform(Matrix4::from_angle_y(Deg(-20.)));
+    let vase = scene.find("vase").unwrap();
+    scene.node_mut(vase).material(Material::dielectric(1.5));
+    let leg = scene.find("leg 3").unwrap();
+    scene.node_mut(leg).visible(false);
+
+    let world = scene.flatten();
+
+    let camera = CameraBuilder::default()
+        .image_width(600)
+        .image_height(400)
+        .samples_per_pixel(100)
+        .max_depth(50)
+        .vfov(30.)
+        .lookfrom(Point3::new(1., 3., 6.))
+        .lookat(Point3::new(0., 0.7, 0.))
+        .build();
+
+    camera.render(&world, "output/scene-graph.png").unwrap();
+}
@@ -0,0 +1,84 @@
+use path_tracer::{
+    camera::CameraBuilder,
+    hittable::{Bvh, Cone, Cylinder, Disk, HittableList, Quad, Torus, Triangle},
+    material::Material,
+    math::{Point3, Vector3},
+    texture::ImageTexture,
+    Color,
+};
+
+fn main() {
+    let mut world = HittableList::new();
+
+    let ground = Material::lambertian(Color::new(0.48, 0.83, 0.53).into());
+    let earth = Material::lambertian(ImageTexture::new("images/earthmap.jpg").unwrap().into());
+    let red = Material::lambertian(Color::new(0.8, 0.2, 0.2).into());
+    let gold = Material::metal(Color::new(0.8, 0.6, 0.2), 0.1);
+    let blue = Material::lambertian(Color::new(0.2, 0.3, 0.8).into());
+    let light = Material::diffuse_light(Color::new(7., 7., 7.).into());
+
+    world.push(Quad::new(
+        Point3::new(-10., 0., -10.),
+        Vector3::new(0., 0., 20.),
+        Vector3::new(20., 0., 0.),
+        ground,
+    ));
+
+    world.push(Cylinder::new(
+        Point3::new(-2.4, 0., 0.),
+        Point3::new(-2.4, 1.6, 0.),
+        0.6,
+        earth.clone(),
+    ));
+    world.push(Cone::new(
+        Point3::new(-0.8, 0., 0.5),
+        Point3::new(-0.8, 1.8, 0.5),
+        0.7,
+        red,
+    ));
+    world.push(Torus::new(
+        Point3::new(0.9, 0.9, 0.),
+        Vector3::new(0., 0.5, 1.),
+        0.7,
+        0.25,
+        gold,
+    ));
+    world.push(
+        Triangle::new(
+            Point3::new(2., 0., -0.5),
+            Point3::new(3.4, 0., -0.8),
+            Point3::new(2.6, 1.8, -1.),
+            blue,
+        )
+        .unwrap(),
+    );
+    world.push(Disk::new(
+        Point3::new(2.8, 0.6, 1.2),
+        Vector3::new(-0.5, 0.6, 1.),
+        0.6,
+        earth,
+    ));
+
+    // A lamp shade over the scene.
+    world.push(Disk::new(
+        Point3::new(0., 5., 2.),
+        Vector3::new(0., -1., 0.),
+        1.5,
+        light,
+    ));
+
+    let world = Bvh::from_list(&mut world);
+
+    let camera = CameraBuilder::default()
+        .image_width(800)
+        .image_height(450)
+        .samples_per_pixel(200)
+        .max_depth(50)
+        .vfov(35.)
+        .lookfrom(Point3::new(0., 3., 9.))
+        .lookat(Point3::new(0.2, 0.8, 0.))
+        .background(Color::new(0.3, 0.35, 0.45))
+        .build();
+
+    camera.render(&world, "output/shapes.png").unwrap();
+}
@@ -0,0 +1,79 @@
+use path_tracer::{
+    animation::{render_sequence, CameraAnimation, Interpolation, Track, TransformAnimation},
+    camera::{CameraBuilder, RenderOptions},
+    hittable::{Bvh, HittableList, Quad, Sphere},
+    material::Material,
+    math::{prelude::*, Deg, Point3, Quaternion, Vector3},
+    Color,
+};
+
+fn main() {
+    let mut camera = CameraBuilder::default();
+    camera
+        .image_width(400)
+        .image_height(300)
+        .samples_per_pixel(50)
+        .max_depth(20)
+        .vfov(35.)
+        .lookat(Point3::new(0., 0.5, 0.))
+        .shutter_open(0.)
+        .shutter_close(1.);
+
+    let mut orbit = Track::new();
+    for i in 0..=8 {
+        let angle = Deg(45. * i as f64);
+        let position = Point3::new(6. * angle.sin(), 2., 6. * angle.cos());
+        orbit = orbit.key(0.5 * i as f64, position, Interpolation::Linear);
+    }
+
+    let mut animation = CameraAnimation::new(camera);
+    animation
+        .lookfrom(orbit)
+        .vfov(
+            Track::new()
+                .key(0., 35., Interpolation::Smooth)
+                .key(4., 25., Interpolation::Smooth),
+        );
+
+    let spin = TransformAnimation::new(
+        Track::new()
+            .key(0., Vector3::new(0., 0., 0.), Interpolation::Smooth)
+            .key(2., Vector3::new(0., 1., 0.), Interpolation::Smooth)
+            .key(4., Vector3::new(0., 0., 0.), Interpolation::Smooth),
+        Track::new()
+            .key(0., Quaternion::one(), Interpolation::Linear)
+            .key(
+                2.,
+                Quaternion::from_angle_y(Deg(180.)),
+                Interpolation::Linear,
+            )
+            .key(
+                4.,
+                Quaternion::from_angle_y(Deg(359.)),
+                Interpolation::Linear,
+            ),
+    );
+
+    let fps = 24.;
+    let options = RenderOptions::default();
+    render_sequence(0..96, fps, "output/turntable", "png", &options, |time| {
+        let mut world = HittableList::new();
+
+        let ground = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        world.push(Sphere::new(Point3::new(0., -1000., 0.), 1000., ground));
+
+        let box_material = Material::metal(Color::new(0.8, 0.6, 0.2), 0.05);
+        let cuboid = Quad::cuboid(
+            Point3::new(-0.5, 0., -0.5),
+            Point3::new(0.5, 1., 0.5),
+            box_material,
+        );
+        world.push(spin.apply_over(cuboid, time..time + fps.recip()));
+
+        let glass = Material::dielectric(1.5);
+        world.push(Sphere::new(Point3::new(1.5, 0.5, 1.), 0.5, glass));
+
+        (animation.at(time).build(), Bvh::from_list(&mut world))
+    })
+    .unwrap();
+}
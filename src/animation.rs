@@ -0,0 +1,335 @@
+use crate::{
+    camera::{Camera, CameraBuilder, RenderOptions},
+    hittable::{Hittable, Transform},
+    lerp,
+    stats::RenderStats,
+};
+use cgmath::{prelude::*, Point3, Quaternion, Vector3};
+use std::{ops::Range, sync::Arc};
+
+/// Curve used between a keyframe and the next one.
+#[derive(Clone, Copy)]
+pub enum Interpolation {
+    /// Hold the value until the next keyframe.
+    Step,
+    Linear,
+    /// Ease in and out (smoothstep).
+    Smooth,
+    EaseIn,
+    EaseOut,
+}
+
+impl Interpolation {
+    fn apply(&self, t: f64) -> f64 {
+        match self {
+            Self::Step => 0.,
+            Self::Linear => t,
+            Self::Smooth => t * t * (3. - 2. * t),
+            Self::EaseIn => t * t,
+            Self::EaseOut => 1. - (1. - t) * (1. - t),
+        }
+    }
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        lerp(a, b, t)
+    }
+}
+
+impl Interpolate for Vector3<f64> {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        lerp(a, b, t)
+    }
+}
+
+impl Interpolate for Point3<f64> {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        a + t * (b - a)
+    }
+}
+
+impl Interpolate for Quaternion<f64> {
+    fn interpolate(a: Self, b: Self, t: f64) -> Self {
+        a.slerp(b, t)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    pub interpolation: Interpolation,
+}
+
+/// Keyframed value over time. Before the first and after the last keyframe the value is held.
+#[derive(Clone)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Default for Track<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new() -> Self {
+        Self { keyframes: vec![] }
+    }
+
+    pub fn key(mut self, time: f64, value: T, interpolation: Interpolation) -> Self {
+        let idx = self.keyframes.partition_point(|key| key.time <= time);
+        self.keyframes.insert(
+            idx,
+            Keyframe {
+                time,
+                value,
+                interpolation,
+            },
+        );
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keyframes.is_empty()
+    }
+
+    /// Value of the track at `time`, or `None` for a track without keyframes.
+    pub fn value(&self, time: f64) -> Option<T> {
+        let idx = self.keyframes.partition_point(|key| key.time <= time);
+
+        if idx == 0 {
+            return self.keyframes.first().map(|key| key.value);
+        }
+        if idx == self.keyframes.len() {
+            return self.keyframes.last().map(|key| key.value);
+        }
+
+        let k0 = &self.keyframes[idx - 1];
+        let k1 = &self.keyframes[idx];
+        let t = (time - k0.time) / (k1.time - k0.time);
+
+        Some(T::interpolate(
+            k0.value,
+            k1.value,
+            k0.interpolation.apply(t),
+        ))
+    }
+}
+
+/// Camera whose parameters are animated on top of a base configuration. Empty tracks keep the
+/// value of the base camera.
+#[derive(Clone)]
+pub struct CameraAnimation {
+    pub camera: CameraBuilder,
+
+    pub lookfrom: Track<Point3<f64>>,
+    pub lookat: Track<Point3<f64>>,
+    pub vfov: Track<f64>,
+    pub defocus_angle: Track<f64>,
+    pub focus_distance: Track<f64>,
+}
+
+impl CameraAnimation {
+    pub fn new(camera: CameraBuilder) -> Self {
+        Self {
+            camera,
+
+            lookfrom: Track::new(),
+            lookat: Track::new(),
+            vfov: Track::new(),
+            defocus_angle: Track::new(),
+            focus_distance: Track::new(),
+        }
+    }
+
+    #[inline]
+    pub fn lookfrom(&mut self, lookfrom: Track<Point3<f64>>) -> &mut Self {
+        self.lookfrom = lookfrom;
+        self
+    }
+
+    #[inline]
+    pub fn lookat(&mut self, lookat: Track<Point3<f64>>) -> &mut Self {
+        self.lookat = lookat;
+        self
+    }
+
+    #[inline]
+    pub fn vfov(&mut self, vfov: Track<f64>) -> &mut Self {
+        self.vfov = vfov;
+        self
+    }
+
+    #[inline]
+    pub fn defocus_angle(&mut self, defocus_angle: Track<f64>) -> &mut Self {
+        self.defocus_angle = defocus_angle;
+        self
+    }
+
+    #[inline]
+    pub fn focus_distance(&mut self, focus_distance: Track<f64>) -> &mut Self {
+        self.focus_distance = focus_distance;
+        self
+    }
+
+    /// Camera configuration at `time`.
+    pub fn at(&self, time: f64) -> CameraBuilder {
+        let mut camera = self.camera.clone();
+
+        if let Some(lookfrom) = self.lookfrom.value(time) {
+            camera.lookfrom(lookfrom);
+        }
+        if let Some(lookat) = self.lookat.value(time) {
+            camera.lookat(lookat);
+        }
+        if let Some(vfov) = self.vfov.value(time) {
+            camera.vfov(vfov);
+        }
+        if let Some(defocus_angle) = self.defocus_angle.value(time) {
+            camera.defocus_angle(defocus_angle);
+        }
+        if let Some(focus_distance) = self.focus_distance.value(time) {
+            camera.focus_distance(focus_distance);
+        }
+
+        camera
+    }
+}
+
+/// Keyframed translation and rotation. Empty tracks stay at the identity.
+#[derive(Clone, Default)]
+pub struct TransformAnimation {
+    pub translation: Track<Vector3<f64>>,
+    pub rotation: Track<Quaternion<f64>>,
+}
+
+impl TransformAnimation {
+    pub fn new(translation: Track<Vector3<f64>>, rotation: Track<Quaternion<f64>>) -> Self {
+        Self {
+            translation,
+            rotation,
+        }
+    }
+
+    pub fn at(&self, time: f64) -> (Vector3<f64>, Quaternion<f64>) {
+        (
+            self.translation.value(time).unwrap_or_else(Vector3::zero),
+            self.rotation.value(time).unwrap_or_else(Quaternion::one),
+        )
+    }
+
+    /// Places `object` where the animation puts it at `time`.
+    #[allow(private_bounds)]
+    pub fn apply<H: Hittable + Send + Sync + 'static>(
+        &self,
+        object: Arc<H>,
+        time: f64,
+    ) -> Arc<Transform> {
+        let (translation, rotation) = self.at(time);
+        Transform::new(object, translation, rotation)
+    }
+
+    /// Moves `object` along the animation from `interval.start` to `interval.end`, mapped to the
+    /// ray times 0 to 1 so that a camera with that shutter renders it with motion blur.
+    #[allow(private_bounds)]
+    pub fn apply_over<H: Hittable + Send + Sync + 'static>(
+        &self,
+        object: Arc<H>,
+        interval: Range<f64>,
+    ) -> Arc<Transform> {
+        Transform::moving(object, self.at(interval.start), self.at(interval.end))
+    }
+}
+
+/// Renders the frames `frames` of an animation playing at `fps` frames per second into
+/// `directory/frame_0001.<extension>` and so on, in the output format of the camera or else the
+/// one of `extension`. `scene` builds the camera and world for a frame from its time in seconds.
+/// Returns the statistics of all frames together.
+///
+/// A frame cancelled through `options` is still written, but no frames follow it.
+#[allow(private_bounds)]
+pub fn render_sequence<H, F>(
+    frames: Range<u32>,
+    fps: f64,
+    directory: &str,
+    extension: &str,
+    options: &RenderOptions,
+    mut scene: F,
+) -> image::ImageResult<RenderStats>
+where
+    H: Hittable + Sync,
+    F: FnMut(f64) -> (Camera, H),
+{
+    std::fs::create_dir_all(directory)?;
+
+    let mut stats = RenderStats::default();
+    for frame in frames {
+        let (camera, world) = scene(frame as f64 / fps);
+        let path = format!("{}/frame_{:04}.{}", directory, frame, extension);
+        let frame_stats = camera.render_with(&world, &path, options)?;
+        stats.merge(&frame_stats);
+        if frame_stats.cancelled {
+            break;
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::HittableList, progress::NoProgress};
+    use std::{fs, path::Path};
+
+    fn sequence_dir(name: &str) -> String {
+        let dir = std::env::temp_dir().join(format!("sequence-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir.to_str().unwrap().to_owned()
+    }
+
+    fn small_frame(_time: f64) -> (Camera, HittableList) {
+        let camera = CameraBuilder::default()
+            .image_width(4)
+            .image_height(3)
+            .samples_per_pixel(1)
+            .build();
+        (camera, HittableList::new())
+    }
+
+    #[test]
+    fn writes_frames_with_extension() {
+        let dir = sequence_dir("extension");
+        let mut options = RenderOptions::default();
+        options.progress(NoProgress);
+
+        render_sequence(1..3, 24., &dir, "pfm", &options, small_frame).unwrap();
+
+        assert!(Path::new(&dir).join("frame_0001.pfm").exists());
+        assert!(Path::new(&dir).join("frame_0002.pfm").exists());
+        assert!(!Path::new(&dir).join("frame_0001.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stops_after_cancelled_frame() {
+        let dir = sequence_dir("cancel");
+        let mut options = RenderOptions::default();
+        options.progress(NoProgress);
+        options.cancellation.cancel();
+
+        let stats = render_sequence(0..3, 24., &dir, "png", &options, small_frame).unwrap();
+
+        assert!(stats.cancelled);
+        assert!(Path::new(&dir).join("frame_0000.png").exists());
+        assert!(!Path::new(&dir).join("frame_0001.png").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{
+    distributed::wire::{invalid_data, unknown_tag, Decode, Decoder, Encode, Encoder},
+    random_in_unit_disk,
+};
+use cgmath::{prelude::*, Vector2};
+use rand::prelude::*;
+use std::f64::consts::PI;
+use std::{io, sync::Arc};
+
+/// Shape of the lens opening, which determines the shape of out-of-focus highlights (bokeh).
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    /// Regular polygon formed by `blades` straight aperture blades, rotated by `rotation` degrees.
+    Polygon {
+        blades: u32,
+        rotation: f64,
+    },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    /// Samples a point of the aperture, scaled to fit into the unit disk.
+    pub(crate) fn sample(&self) -> Vector2<f64> {
+        match self {
+            Self::Circle => random_in_unit_disk(),
+            Self::Polygon { blades, rotation } => sample_polygon(*blades, *rotation),
+            Self::Mask(mask) => mask.sample(),
+        }
+    }
+}
+
+fn sample_polygon(blades: u32, rotation: f64) -> Vector2<f64> {
+    let mut rng = rand::thread_rng();
+
+    let blades = blades.max(3);
+    let step = 2. * PI / (blades as f64);
+    let k = rng.gen_range(0..blades) as f64;
+
+    let theta0 = rotation.to_radians() + k * step;
+    let theta1 = theta0 + step;
+    let v0 = Vector2::new(theta0.cos(), theta0.sin());
+    let v1 = Vector2::new(theta1.cos(), theta1.sin());
+
+    // Uniform point in the triangle spanned by the center and one edge of the polygon.
+    let mut a = rng.gen::<f64>();
+    let mut b = rng.gen::<f64>();
+    if a + b > 1. {
+        a = 1. - a;
+        b = 1. - b;
+    }
+
+    a * v0 + b * v1
+}
+
+/// Custom aperture shape read from a grayscale image. The image is stretched over the square
+/// enclosing the unit disk, and brighter texels transmit more light.
+pub struct ApertureMask {
+    width: u32,
+    height: u32,
+    cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+    pub fn new(path: &str) -> std::io::Result<Self> {
+        use image::io::Reader as ImageReader;
+
+        let image = ImageReader::open(path)?
+            .decode()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?
+            .into_luma8();
+
+        let mut cdf = Vec::with_capacity((image.width() * image.height()) as usize);
+        let mut total = 0.;
+        for pixel in image.pixels() {
+            total += pixel.0[0] as f64;
+            cdf.push(total);
+        }
+
+        Ok(Self {
+            width: image.width(),
+            height: image.height(),
+            cdf,
+        })
+    }
+
+    fn sample(&self) -> Vector2<f64> {
+        let total = self.cdf.last().copied().unwrap_or(0.);
+        if total <= 0. {
+            return Vector2::zero();
+        }
+
+        let mut rng = rand::thread_rng();
+        let idx = self.cdf.partition_point(|&c| c <= rng.gen::<f64>() * total) as u32;
+
+        let x = (idx % self.width) as f64 + rng.gen::<f64>();
+        let y = (idx / self.width) as f64 + rng.gen::<f64>();
+
+        Vector2::new(
+            2. * x / (self.width as f64) - 1.,
+            1. - 2. * y / (self.height as f64),
+        )
+    }
+}
+
+impl Encode for Aperture {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Self::Circle => encoder.put(&0u8),
+            Self::Polygon { blades, rotation } => encoder.put(&1u8).put(blades).put(rotation),
+            Self::Mask(mask) => encoder.put(&2u8).put(mask),
+        };
+    }
+}
+
+impl Decode for Aperture {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::Circle,
+            1 => Self::Polygon {
+                blades: decoder.get()?,
+                rotation: decoder.get()?,
+            },
+            2 => Self::Mask(decoder.get()?),
+            tag => return Err(unknown_tag("aperture", tag)),
+        })
+    }
+}
+
+impl Encode for ApertureMask {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.width).put(&self.height).put(&self.cdf);
+    }
+}
+
+impl Decode for ApertureMask {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let width: u32 = decoder.get()?;
+        let height: u32 = decoder.get()?;
+        let cdf: Vec<f64> = decoder.get()?;
+        if cdf.len() as u64 != width as u64 * height as u64 {
+            return Err(invalid_data("aperture mask size mismatch"));
+        }
+
+        Ok(Self { width, height, cdf })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraBuilder;
+    use cgmath::{Point3, Vector3};
+
+    const SAMPLES: usize = 10_000;
+
+    #[test]
+    fn polygon_samples_stay_inside_the_polygon() {
+        for (blades, rotation) in [(3, 0.), (5, 18.), (6, 90.)] {
+            let aperture = Aperture::Polygon { blades, rotation };
+            let step = 2. * PI / blades as f64;
+            // Distance from the center to the middle of each edge.
+            let apothem = (step / 2.).cos();
+            for _ in 0..SAMPLES {
+                let p = aperture.sample();
+                for k in 0..blades {
+                    let theta = rotation.to_radians() + (k as f64 + 0.5) * step;
+                    let normal = Vector2::new(theta.cos(), theta.sin());
+                    assert!(p.dot(normal) <= apothem + 1e-12);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mask_samples_stay_inside_transmitting_texels() {
+        // Only the top right texel of a 4 by 4 mask lets light through.
+        let mut cdf = vec![0.; 3];
+        cdf.extend([255.; 13]);
+        let aperture = Aperture::Mask(Arc::new(ApertureMask {
+            width: 4,
+            height: 4,
+            cdf,
+        }));
+
+        for _ in 0..SAMPLES {
+            let p = aperture.sample();
+            assert!((0.5..=1.).contains(&p.x), "{:?}", p);
+            assert!((0.5..=1.).contains(&p.y), "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn rays_converge_at_focus_distance() {
+        let focus_dist = 5.;
+        let mut builder = CameraBuilder::default();
+        builder
+            .image_width(8)
+            .image_height(6)
+            .lookfrom(Point3::new(1., 2., 3.))
+            .lookat(Point3::new(1., 2., 0.))
+            .defocus_angle(10.)
+            .focus_distance(focus_dist)
+            .aperture(Aperture::Polygon {
+                blades: 6,
+                rotation: 0.,
+            });
+        let camera = builder.build();
+        let [u, v, w] = camera.basis;
+        let defocus_radius = camera.defocus_disk_u.magnitude();
+
+        for (i, j) in [(0, 0), (3, 2), (7, 5)] {
+            let pixel_center = camera.pixel00_loc
+                + (i as f64) * camera.pixel_delta_u
+                + (j as f64) * camera.pixel_delta_v;
+            let mut spread: f64 = 0.;
+            for _ in 0..1000 {
+                let ray = camera.get_ray(i, j).unwrap();
+                let offset: Vector3<f64> = ray.origin - camera.center;
+                assert!(offset.dot(w).abs() < 1e-12);
+                spread = spread.max(offset.magnitude());
+
+                let t = focus_dist / ray.direction.dot(-w);
+                let focused = ray.at(t) - pixel_center;
+                assert!(focused.dot(u).abs() <= 0.5 * camera.pixel_delta_u.magnitude() + 1e-9);
+                assert!(focused.dot(v).abs() <= 0.5 * camera.pixel_delta_v.magnitude() + 1e-9);
+            }
+            // The rays do leave from all over the lens, not just its center.
+            assert!(spread > 0.5 * defocus_radius);
+        }
+    }
+}
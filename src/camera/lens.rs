@@ -0,0 +1,309 @@
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    random_in_unit_disk, refract,
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use std::io;
+
+/// One row of a lens prescription, listed from the object side to the film side.
+#[derive(Clone, Copy)]
+pub struct LensElement {
+    /// Radius of curvature of the interface, positive when its center lies towards the film.
+    /// Zero for a flat interface or the aperture stop.
+    pub radius: f64,
+    /// Axial distance to the next interface. Ignored for the last row, whose distance to the film
+    /// is chosen when focusing.
+    pub thickness: f64,
+    /// Index of refraction of the medium behind the interface, or zero for the aperture stop.
+    pub ior: f64,
+    /// Diameter of the interface.
+    pub aperture: f64,
+}
+
+/// Sequence of spherical lens interfaces in front of a film. Rays are traced from the film
+/// through every interface, so distortion, vignetting and focus breathing come from the
+/// prescription itself.
+///
+/// Lens space has the film centered at the origin and the optical axis along +z towards the
+/// scene.
+pub struct LensSystem {
+    elements: Vec<LensElement>,
+    film_diagonal: f64,
+}
+
+impl LensSystem {
+    /// `scale` converts the units of the prescription and `film_diagonal` (often millimeters)
+    /// into scene units.
+    pub fn new(elements: Vec<LensElement>, film_diagonal: f64, scale: f64) -> Self {
+        let elements = elements
+            .into_iter()
+            .map(|element| LensElement {
+                radius: element.radius * scale,
+                thickness: element.thickness * scale,
+                ior: element.ior,
+                aperture: element.aperture * scale,
+            })
+            .collect();
+
+        Self {
+            elements,
+            film_diagonal: film_diagonal * scale,
+        }
+    }
+
+    pub(crate) fn film_size(&self, aspect_ratio: f64) -> (f64, f64) {
+        let height = self.film_diagonal / (1. + aspect_ratio * aspect_ratio).sqrt();
+        (aspect_ratio * height, height)
+    }
+
+    /// Film distance behind the rear interface that brings objects at `distance` from the film
+    /// into focus.
+    pub(crate) fn focus(&self, distance: f64) -> f64 {
+        let mut lo = 0.;
+        let mut hi = self.elements.last().map_or(1., |rear| rear.aperture);
+        for _ in 0..64 {
+            if self.conjugate_distance(hi) <= distance {
+                break;
+            }
+            hi *= 2.;
+        }
+
+        for _ in 0..64 {
+            let mid = 0.5 * (lo + hi);
+            if self.conjugate_distance(mid) > distance {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+
+        hi
+    }
+
+    /// Distance from the film at which a paraxial ray leaving the film center crosses the axis
+    /// again, i.e. the object distance in focus for the given film distance.
+    fn conjugate_distance(&self, film_distance: f64) -> f64 {
+        let rear = match self.elements.last() {
+            Some(rear) => rear,
+            None => return f64::INFINITY,
+        };
+
+        let height = 1e-3 * rear.aperture;
+        let direction = Vector3::new(height, 0., film_distance);
+        let (origin, direction) =
+            match self.trace_from_film(film_distance, Point3::origin(), direction) {
+                Some(ray) => ray,
+                None => return f64::INFINITY,
+            };
+
+        if origin.x * direction.x >= 0. {
+            return f64::INFINITY;
+        }
+
+        origin.z - origin.x * direction.z / direction.x
+    }
+
+    /// Random point of the rear interface, the target of rays leaving the film.
+    pub(crate) fn sample_rear(&self, film_distance: f64) -> Point3<f64> {
+        let radius = self.elements.last().map_or(0., |rear| 0.5 * rear.aperture);
+        let p = radius * random_in_unit_disk();
+
+        Point3::new(p.x, p.y, film_distance)
+    }
+
+    /// Traces a ray leaving the film through all interfaces, returning the ray leaving the front
+    /// element, or `None` when it is blocked or totally internally reflected.
+    pub(crate) fn trace_from_film(
+        &self,
+        film_distance: f64,
+        mut origin: Point3<f64>,
+        direction: Vector3<f64>,
+    ) -> Option<(Point3<f64>, Vector3<f64>)> {
+        let mut direction = direction.normalize();
+        let mut z = film_distance;
+
+        for (idx, element) in self.elements.iter().enumerate().rev() {
+            if idx + 1 < self.elements.len() {
+                z += element.thickness;
+            }
+
+            let (t, normal) = if element.radius == 0. {
+                let t = (z - origin.z) / direction.z;
+                (t, Vector3::unit_z())
+            } else {
+                let center = Point3::new(0., 0., z - element.radius);
+                let t = intersect_interface(origin, direction, center, element.radius)?;
+                (t, (origin + t * direction - center).normalize())
+            };
+            if t.is_nan() || t <= 0. {
+                return None;
+            }
+
+            origin += t * direction;
+            if origin.x * origin.x + origin.y * origin.y > 0.25 * element.aperture.powi(2) {
+                return None;
+            }
+
+            if element.ior == 0. {
+                continue;
+            }
+
+            let normal = if normal.dot(direction) > 0. {
+                -normal
+            } else {
+                normal
+            };
+            let eta_i = element.ior;
+            let eta_t = match idx {
+                0 => 1.,
+                _ if self.elements[idx - 1].ior == 0. => 1.,
+                _ => self.elements[idx - 1].ior,
+            };
+            let ratio = eta_i / eta_t;
+
+            let cos_theta = -direction.dot(normal);
+            if ratio * ratio * (1. - cos_theta * cos_theta) > 1. {
+                return None;
+            }
+            direction = refract(direction, normal, ratio).normalize();
+        }
+
+        Some((origin, direction))
+    }
+}
+
+/// Ray parameter of the intersection with the spherical cap around the interface vertex.
+fn intersect_interface(
+    origin: Point3<f64>,
+    direction: Vector3<f64>,
+    center: Point3<f64>,
+    radius: f64,
+) -> Option<f64> {
+    let oc = origin - center;
+    let half_b = oc.dot(direction);
+    let c = oc.magnitude2() - radius * radius;
+
+    let discriminant = half_b * half_b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+
+    // The vertex is the point of the sphere farthest towards the scene for a positive radius,
+    // and the nearest one for a negative radius.
+    let use_closer = (direction.z > 0.) != (radius > 0.);
+    if use_closer {
+        Some(-half_b - sqrtd)
+    } else {
+        Some(-half_b + sqrtd)
+    }
+}
+
+impl Encode for LensElement {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.radius)
+            .put(&self.thickness)
+            .put(&self.ior)
+            .put(&self.aperture);
+    }
+}
+
+impl Decode for LensElement {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            radius: decoder.get()?,
+            thickness: decoder.get()?,
+            ior: decoder.get()?,
+            aperture: decoder.get()?,
+        })
+    }
+}
+
+// Sent already scaled, so decoding does not go through `new`.
+impl Encode for LensSystem {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.elements).put(&self.film_diagonal);
+    }
+}
+
+impl Decode for LensSystem {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            elements: decoder.get()?,
+            film_diagonal: decoder.get()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f64 = 50.;
+    const IOR: f64 = 1.5;
+    // Focal length of a thin plano-convex element, from the lensmaker's equation.
+    const FOCAL_LENGTH: f64 = RADIUS / (IOR - 1.);
+    // Just thick enough for the curved face to stay in front of the flat one across the aperture.
+    const THICKNESS: f64 = 0.02;
+    const APERTURE: f64 = 2.;
+
+    fn thin_lens(stop: Option<f64>) -> LensSystem {
+        let mut elements = vec![];
+        if let Some(aperture) = stop {
+            elements.push(LensElement {
+                radius: 0.,
+                thickness: 1.,
+                ior: 0.,
+                aperture,
+            });
+        }
+        elements.push(LensElement {
+            radius: RADIUS,
+            thickness: THICKNESS,
+            ior: IOR,
+            aperture: APERTURE,
+        });
+        elements.push(LensElement {
+            radius: 0.,
+            thickness: 0.,
+            ior: 1.,
+            aperture: APERTURE,
+        });
+
+        LensSystem::new(elements, 43.3, 1.)
+    }
+
+    #[test]
+    fn thin_element_focuses_at_the_thin_lens_distance() {
+        let lens = thin_lens(None);
+
+        for object in [300., 1000., 1e9] {
+            // 1/object + 1/image = 1/f
+            let image = 1. / (1. / FOCAL_LENGTH - 1. / object);
+            let film_distance = lens.focus(object + THICKNESS + image);
+            assert!(
+                (film_distance - image).abs() < 0.05,
+                "{} instead of {}",
+                film_distance,
+                image
+            );
+        }
+    }
+
+    #[test]
+    fn rays_blocked_by_the_stop_are_rejected() {
+        let film_distance = 150.;
+        let through = |lens: &LensSystem, height: f64| {
+            let target = Point3::new(height, 0., film_distance);
+            lens.trace_from_film(film_distance, Point3::origin(), target - Point3::origin())
+        };
+
+        let open = thin_lens(None);
+        let stopped = thin_lens(Some(0.5));
+        assert!(through(&open, 0.1).is_some());
+        assert!(through(&stopped, 0.1).is_some());
+        assert!(through(&open, 0.8).is_some());
+        assert!(through(&stopped, 0.8).is_none());
+    }
+}
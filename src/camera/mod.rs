@@ -0,0 +1,1132 @@
+use crate::{
+    colorspace::ColorSpace,
+    distributed::wire::{unknown_tag, Decode, Decoder, Encode, Encoder},
+    film::{CropWindow, Film, OutputFormat},
+    hittable::{Hittable, Seen},
+    post::PostEffect,
+    progress::{Pass, Tracker},
+    ray::{Ray, SampleClamp},
+    stats::{RenderStats, Tally},
+    tonemap::{ToneMapper, ToneMapping},
+    Color,
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use rand::prelude::*;
+use rayon::prelude::*;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod aperture;
+mod lens;
+mod options;
+mod panorama;
+
+pub use aperture::{Aperture, ApertureMask};
+pub use lens::{LensElement, LensSystem};
+pub use options::{CancellationToken, RenderOptions};
+pub use panorama::FisheyeMapping;
+
+/// Number of consecutive pixels a thread renders in one go, and between which it adds what it
+/// counted to the stats of the render.
+const TILE_PIXELS: usize = 64;
+
+/// Samples per pixel of a render without a budget whose samples per pixel are not set.
+pub(crate) const DEFAULT_SAMPLES_PER_PIXEL: u32 = 10;
+
+pub struct Camera {
+    image_width: u32,
+    image_height: u32,
+    samples_per_pixel: Option<u32>,
+    max_depth: u32,
+    background: Color,
+    clamp: SampleClamp,
+
+    samples_per_pass: u32,
+    time_budget: Option<Duration>,
+    noise_target: Option<f64>,
+    outlier_rejection: Option<f64>,
+
+    projection: Projection,
+    center: Point3<f64>,
+    basis: [Vector3<f64>; 3],
+    focus_offset: Vector3<f64>,
+    film_distance: f64,
+    pixel_delta_u: Vector3<f64>,
+    pixel_delta_v: Vector3<f64>,
+    pixel00_loc: Point3<f64>,
+
+    defocus_disk_u: Vector3<f64>,
+    defocus_disk_v: Vector3<f64>,
+    aperture: Aperture,
+
+    shutter_open: f64,
+    shutter_close: f64,
+
+    crop: CropWindow,
+    crop_output: CropOutput,
+
+    post_effects: Vec<PostEffect>,
+    tone_mapping: ToneMapping,
+    working_space: ColorSpace,
+    output_format: Option<OutputFormat>,
+}
+
+impl Camera {
+    /// Renders `world` to the image file at `path` with a progress bar, and prints a summary of
+    /// the statistics of the render.
+    #[allow(private_bounds)]
+    pub fn render<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        path: &str,
+    ) -> image::ImageResult<RenderStats> {
+        self.render_with(world, path, &RenderOptions::default())
+    }
+
+    /// Renders `world` to the image file at `path`. A render cancelled through `options` still
+    /// writes the pixels rendered so far, and its statistics are marked as cancelled.
+    #[allow(private_bounds)]
+    pub fn render_with<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        path: &str,
+        options: &RenderOptions,
+    ) -> image::ImageResult<RenderStats> {
+        let (film, mut stats) = self.render_passes(world, options);
+        let format = self
+            .output_format
+            .unwrap_or_else(|| OutputFormat::from_path(path));
+
+        let start = Instant::now();
+        match self.crop_output {
+            CropOutput::Cropped => {
+                film.save_as(path, format, &self.post_effects, &self.tone_mapping)?
+            }
+            CropOutput::FullFrame => {
+                film.save_full_frame_as(path, format, &self.post_effects, &self.tone_mapping)?
+            }
+        }
+        stats.write = start.elapsed();
+
+        options.progress.finished(&stats);
+
+        Ok(stats)
+    }
+
+    /// Renders `world` into a film without writing it, e.g. to merge it with other films.
+    #[allow(private_bounds)]
+    pub fn render_film<H: Hittable + Sync>(&self, world: &H) -> (Film, RenderStats) {
+        self.render_film_with(world, &RenderOptions::default())
+    }
+
+    /// Renders `world` into a film. A render cancelled through `options` returns as soon as the
+    /// render threads notice, with the samples taken so far; pixels may then have different numbers
+    /// of samples, see [`Film::samples`].
+    #[allow(private_bounds)]
+    pub fn render_film_with<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        options: &RenderOptions,
+    ) -> (Film, RenderStats) {
+        let (film, stats) = self.render_passes(world, options);
+        options.progress.finished(&stats);
+
+        (film, stats)
+    }
+
+    fn render_passes<H: Hittable + Sync>(
+        &self,
+        world: &H,
+        options: &RenderOptions,
+    ) -> (Film, RenderStats) {
+        let tally = Tally::new();
+        let bvh_build = world.build_time(&mut Seen::new());
+
+        let mut film = Film::new(
+            self.crop,
+            self.image_width,
+            self.image_height,
+            self.working_space,
+        );
+        let background = self.working_space.from_linear_srgb(self.background);
+        if let Some(sigmas) = self.outlier_rejection {
+            film.reject_outliers(sigmas);
+        }
+
+        let pixels = (self.crop.width * self.crop.height) as u64;
+        options.progress.started(pixels);
+        // The amount of work of budgeted renders is only known as they go.
+        let total = if self.has_budget() {
+            0
+        } else {
+            pixels * self.max_samples() as u64
+        };
+        let tracker = Tracker::new(options.progress.as_ref(), total);
+
+        let start = Instant::now();
+        let mut samples = 0;
+        while let Some(pass_samples) = self.next_pass(&film, samples, start) {
+            let window = film.window();
+            let outlier_rejection = film.outlier_rejection();
+            let cancellation = &options.cancellation;
+            film.pixels
+                .par_chunks_mut(TILE_PIXELS)
+                .enumerate()
+                .for_each(|(tile, pixels)| {
+                    tally.record(|| {
+                        for (offset, pixel) in pixels.iter_mut().enumerate() {
+                            if cancellation.is_cancelled() {
+                                return;
+                            }
+
+                            let idx = (tile * TILE_PIXELS + offset) as u32;
+                            let i = window.x + idx % window.width;
+                            let j = window.y + idx / window.width;
+                            for _ in 0..pass_samples {
+                                let Some(ray) = self.get_ray(i, j) else {
+                                    pixel.add_sample(Color::zero(), outlier_rejection);
+                                    continue;
+                                };
+
+                                let (color, features) = ray.trace(
+                                    world,
+                                    &background,
+                                    self.max_depth,
+                                    &self.clamp,
+                                    self.working_space,
+                                );
+                                pixel.add_sample(color, outlier_rejection);
+                                if let Some(features) = features {
+                                    pixel.add_features(features);
+                                }
+                            }
+                        }
+                    });
+
+                    tracker.advance(pixels.len() as u64 * pass_samples as u64);
+                });
+
+            if cancellation.is_cancelled() {
+                break;
+            }
+
+            samples += pass_samples;
+            if self.has_budget() {
+                tracker.report(self.budget_fraction(&film, samples, start));
+            }
+
+            options.progress.pass(&Pass {
+                film: &film,
+                samples,
+                post_effects: &self.post_effects,
+                tone_mapping: &self.tone_mapping,
+                stats: tally.stats(bvh_build, start.elapsed()),
+            });
+        }
+
+        let mut stats = tally.stats(bvh_build, start.elapsed());
+        stats.cancelled = options.cancellation.is_cancelled();
+
+        (film, stats)
+    }
+
+    #[inline]
+    fn has_budget(&self) -> bool {
+        self.time_budget.is_some() || self.noise_target.is_some()
+    }
+
+    /// Most samples per pixel the render takes: the ones set, or else as many as the budget
+    /// needs.
+    #[inline]
+    fn max_samples(&self) -> u32 {
+        self.samples_per_pixel.unwrap_or(if self.has_budget() {
+            u32::MAX
+        } else {
+            DEFAULT_SAMPLES_PER_PIXEL
+        })
+    }
+
+    /// Estimated fraction of a budgeted render that is done after `samples` per pixel.
+    fn budget_fraction(&self, film: &Film, samples: u32, start: Instant) -> f64 {
+        let mut fraction = samples as f64 / self.max_samples() as f64;
+        if let Some(budget) = self.time_budget {
+            fraction = fraction.max(start.elapsed().as_secs_f64() / budget.as_secs_f64());
+        }
+        if let Some(target) = self.noise_target {
+            // Noise falls with the square root of the number of samples.
+            fraction = fraction.max((target / film.noise()).powi(2));
+        }
+
+        fraction.min(1.)
+    }
+
+    /// Number of samples per pixel to add in the next pass, or `None` once the render is done.
+    fn next_pass(&self, film: &Film, samples: u32, start: Instant) -> Option<u32> {
+        // Budgets keep a render going until they are met, but never past samples set explicitly.
+        let remaining = self.max_samples().saturating_sub(samples);
+        if remaining == 0 {
+            return None;
+        }
+
+        if samples > 0 {
+            if let Some(target) = self.noise_target {
+                if film.noise() <= target {
+                    return None;
+                }
+            }
+
+            if let Some(budget) = self.time_budget {
+                // Stop early rather than overshoot: assume the next pass takes as long as the average one.
+                let elapsed = start.elapsed();
+                let per_pass = elapsed / samples.div_ceil(self.samples_per_pass);
+                if elapsed + per_pass > budget {
+                    return None;
+                }
+            }
+        }
+
+        Some(remaining.min(self.samples_per_pass))
+    }
+
+    fn get_ray(&self, i: u32, j: u32) -> Option<Ray> {
+        match &self.projection {
+            Projection::Perspective | Projection::Orthographic { .. } => {
+                Some(self.get_planar_ray(i, j))
+            }
+            Projection::Realistic(lens) => self.get_lens_ray(lens, i, j),
+            _ => self.get_panoramic_ray(i, j),
+        }
+    }
+
+    fn get_planar_ray(&self, i: u32, j: u32) -> Ray {
+        let pixel_center =
+            self.pixel00_loc + (i as f64) * self.pixel_delta_u + (j as f64) * self.pixel_delta_v;
+        let pixel_sample = pixel_center + self.pixel_sample_square();
+
+        let lens_center = match self.projection {
+            Projection::Orthographic { .. } => pixel_sample + self.focus_offset,
+            _ => self.center,
+        };
+        let origin = lens_center + self.defocus_disk_sample();
+        let direction = pixel_sample - origin;
+
+        Ray {
+            origin,
+            direction,
+            time: self.shutter_sample(),
+        }
+    }
+
+    fn get_panoramic_ray(&self, i: u32, j: u32) -> Option<Ray> {
+        let mut rng = rand::thread_rng();
+
+        let x = (i as f64) + rng.gen::<f64>();
+        let y = (j as f64) + rng.gen::<f64>();
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+
+        let local = match &self.projection {
+            Projection::Equirectangular => panorama::equirectangular(x / width, y / height),
+            Projection::Cubemap => panorama::cubemap(x / width, y / height),
+            Projection::Fisheye { fov, mapping } => {
+                let radius = 0.5 * width.min(height);
+                let x = (x - 0.5 * width) / radius;
+                let y = (0.5 * height - y) / radius;
+                panorama::fisheye(x, y, *fov, *mapping)?
+            }
+            _ => unreachable!(),
+        };
+
+        let [u, v, w] = self.basis;
+        let direction = local.x * u + local.y * v - local.z * w;
+
+        Some(Ray {
+            origin: self.center,
+            direction,
+            time: self.shutter_sample(),
+        })
+    }
+
+    fn get_lens_ray(&self, lens: &LensSystem, i: u32, j: u32) -> Option<Ray> {
+        let mut rng = rand::thread_rng();
+
+        let width = self.image_width as f64;
+        let height = self.image_height as f64;
+        let s = ((i as f64) + rng.gen::<f64>()) / width;
+        let t = ((j as f64) + rng.gen::<f64>()) / height;
+
+        // The lens flips the image, so the top left pixel sits at the bottom right of the film.
+        let (film_width, film_height) = lens.film_size(width / height);
+        let film_point = Point3::new((0.5 - s) * film_width, (t - 0.5) * film_height, 0.);
+        let rear_point = lens.sample_rear(self.film_distance);
+
+        let (origin, direction) =
+            lens.trace_from_film(self.film_distance, film_point, rear_point - film_point)?;
+
+        let [u, v, w] = self.basis;
+        Some(Ray {
+            origin: self.center + origin.x * u + origin.y * v - origin.z * w,
+            direction: direction.x * u + direction.y * v - direction.z * w,
+            time: self.shutter_sample(),
+        })
+    }
+
+    fn shutter_sample(&self) -> f64 {
+        if self.shutter_open < self.shutter_close {
+            rand::thread_rng().gen_range(self.shutter_open..self.shutter_close)
+        } else {
+            self.shutter_open
+        }
+    }
+
+    fn pixel_sample_square(&self) -> Vector3<f64> {
+        let mut rng = rand::thread_rng();
+
+        let px = rng.gen_range(-0.5..0.5);
+        let py = rng.gen_range(-0.5..0.5);
+
+        px * self.pixel_delta_u + py * self.pixel_delta_v
+    }
+
+    fn defocus_disk_sample(&self) -> Vector3<f64> {
+        let p = self.aperture.sample();
+        p.x * self.defocus_disk_u + p.y * self.defocus_disk_v
+    }
+}
+
+#[derive(Clone)]
+pub struct CameraBuilder {
+    pub image_width: u32,
+    pub image_height: u32,
+    /// Samples per pixel, see [`CameraBuilder::samples_per_pixel`].
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: u32,
+    /// Linear sRGB color seen by rays that leave the scene.
+    pub background: Color,
+    /// Per-sample radiance limits of direct and indirect lighting, see
+    /// [`CameraBuilder::clamp_direct`].
+    pub clamp_direct: Option<f64>,
+    pub clamp_indirect: Option<f64>,
+
+    pub samples_per_pass: u32,
+    pub time_budget: Option<Duration>,
+    pub noise_target: Option<f64>,
+    /// See [`Film::reject_outliers`].
+    pub outlier_rejection: Option<f64>,
+
+    pub projection: Projection,
+    pub vfov: f64,
+    pub lookat: Point3<f64>,
+    pub lookfrom: Point3<f64>,
+    pub vup: Vector3<f64>,
+
+    pub defocus_angle: f64,
+    pub focus: Focus,
+    pub aperture: Aperture,
+
+    /// Exposure interval. Moving geometry travels along its path between times 0 and 1.
+    pub shutter_open: f64,
+    pub shutter_close: f64,
+
+    /// Part of the image to render; pixel coordinates stay those of the full image.
+    pub crop: Option<CropWindow>,
+    pub crop_output: CropOutput,
+
+    /// Effects applied in order to the rendered image before tone mapping.
+    pub post_effects: Vec<PostEffect>,
+    pub tone_mapping: ToneMapping,
+    /// Linear color space light transport is computed in, see [`CameraBuilder::working_space`].
+    pub working_space: ColorSpace,
+    /// Format written by [`Camera::render`]; `None` picks it from the file extension.
+    pub output_format: Option<OutputFormat>,
+}
+
+/// What [`Camera::render`] writes when only a crop window is rendered.
+#[derive(Clone, Copy)]
+pub enum CropOutput {
+    /// An image of the size of the crop window.
+    Cropped,
+    /// An image of the full size with only the crop window filled.
+    FullFrame,
+}
+
+/// How the camera maps the scene onto the image.
+#[derive(Clone)]
+pub enum Projection {
+    /// Pinhole or thin-lens camera with a field of view of `vfov`.
+    Perspective,
+    /// Parallel rays covering `view_width` scene units horizontally; `vfov` is ignored.
+    Orthographic { view_width: f64 },
+    /// Full 360x180 degree lat-long panorama, best rendered at a 2:1 aspect ratio.
+    Equirectangular,
+    /// Six 90 degree faces laid out in a 3x2 grid (+X -X +Y / -Y +Z -Z, in the camera frame), best
+    /// rendered at a 3:2 aspect ratio.
+    Cubemap,
+    /// Circular fisheye image covering `fov` degrees across the largest inscribed circle.
+    Fisheye { fov: f64, mapping: FisheyeMapping },
+    /// Rays traced through the interfaces of a real lens; `vfov`, `defocus_angle` and `aperture`
+    /// are ignored since the lens determines them. The film sits at `lookfrom`.
+    Realistic(Arc<LensSystem>),
+}
+
+/// Where the camera is focused.
+#[derive(Clone, Copy)]
+pub enum Focus {
+    LookAt,
+    /// Distance from `lookfrom`, measured along the view direction.
+    Distance(f64),
+    Point(Point3<f64>),
+}
+
+impl Default for CameraBuilder {
+    fn default() -> Self {
+        Self {
+            image_width: 600,
+            image_height: 600,
+            samples_per_pixel: None,
+            max_depth: 10,
+
+            samples_per_pass: 4,
+            time_budget: None,
+            noise_target: None,
+            outlier_rejection: None,
+
+            projection: Projection::Perspective,
+            vfov: 90.,
+            lookat: Point3::origin(),
+            lookfrom: Point3::new(0., 0., -1.),
+            vup: Vector3::unit_y(),
+
+            defocus_angle: 0.,
+            focus: Focus::LookAt,
+            aperture: Aperture::Circle,
+
+            shutter_open: 0.,
+            shutter_close: 0.,
+
+            crop: None,
+            crop_output: CropOutput::Cropped,
+
+            post_effects: vec![],
+            tone_mapping: ToneMapping::default(),
+            working_space: ColorSpace::default(),
+            output_format: None,
+
+            background: Color::new(0.7, 0.8, 1.),
+            clamp_direct: None,
+            clamp_indirect: None,
+        }
+    }
+}
+
+impl CameraBuilder {
+    #[inline]
+    pub fn image_width(&mut self, image_width: u32) -> &mut Self {
+        self.image_width = image_width;
+        self
+    }
+
+    #[inline]
+    pub fn image_height(&mut self, image_height: u32) -> &mut Self {
+        self.image_height = image_height;
+        self
+    }
+
+    /// Samples per pixel of the render, or the most a render with a budget takes. Renders without
+    /// a budget take 10 unless set, and renders with one as many as the budget needs.
+    #[inline]
+    pub fn samples_per_pixel(&mut self, samples_per_pixel: u32) -> &mut Self {
+        self.samples_per_pixel = Some(samples_per_pixel);
+        self
+    }
+
+    #[inline]
+    pub fn max_depth(&mut self, max_depth: u32) -> &mut Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    #[inline]
+    pub fn samples_per_pass(&mut self, samples_per_pass: u32) -> &mut Self {
+        self.samples_per_pass = samples_per_pass;
+        self
+    }
+
+    /// Keep adding sample passes until the wall-clock budget is spent, or until
+    /// `samples_per_pixel` were taken if those are set.
+    #[inline]
+    pub fn time_budget(&mut self, time_budget: Duration) -> &mut Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+
+    /// Keep adding sample passes until the estimated noise level of the image (see
+    /// [`Film::noise`]) drops below `noise_target`, or until `samples_per_pixel` were taken if
+    /// those are set. Combine with a time budget or samples per pixel to bound the render time.
+    #[inline]
+    pub fn noise_target(&mut self, noise_target: f64) -> &mut Self {
+        self.noise_target = Some(noise_target);
+        self
+    }
+
+    #[inline]
+    pub fn outlier_rejection(&mut self, sigmas: f64) -> &mut Self {
+        self.outlier_rejection = Some(sigmas);
+        self
+    }
+
+    #[inline]
+    pub fn projection(&mut self, projection: Projection) -> &mut Self {
+        self.projection = projection;
+        self
+    }
+
+    #[inline]
+    pub fn vfov(&mut self, vfov: f64) -> &mut Self {
+        self.vfov = vfov;
+        self
+    }
+
+    #[inline]
+    pub fn lookat(&mut self, lookat: Point3<f64>) -> &mut Self {
+        self.lookat = lookat;
+        self
+    }
+
+    #[inline]
+    pub fn lookfrom(&mut self, lookfrom: Point3<f64>) -> &mut Self {
+        self.lookfrom = lookfrom;
+        self
+    }
+
+    #[inline]
+    pub fn vup(&mut self, vup: Vector3<f64>) -> &mut Self {
+        self.vup = vup;
+        self
+    }
+
+    #[inline]
+    pub fn defocus_angle(&mut self, defocus_angle: f64) -> &mut Self {
+        self.defocus_angle = defocus_angle;
+        self
+    }
+
+    #[inline]
+    pub fn focus_distance(&mut self, focus_distance: f64) -> &mut Self {
+        self.focus = Focus::Distance(focus_distance);
+        self
+    }
+
+    #[inline]
+    pub fn focus_point(&mut self, focus_point: Point3<f64>) -> &mut Self {
+        self.focus = Focus::Point(focus_point);
+        self
+    }
+
+    #[inline]
+    pub fn aperture(&mut self, aperture: Aperture) -> &mut Self {
+        self.aperture = aperture;
+        self
+    }
+
+    #[inline]
+    pub fn shutter_open(&mut self, shutter_open: f64) -> &mut Self {
+        self.shutter_open = shutter_open;
+        self
+    }
+
+    #[inline]
+    pub fn shutter_close(&mut self, shutter_close: f64) -> &mut Self {
+        self.shutter_close = shutter_close;
+        self
+    }
+
+    #[inline]
+    pub fn crop(&mut self, crop: CropWindow) -> &mut Self {
+        self.crop = Some(crop);
+        self
+    }
+
+    #[inline]
+    pub fn crop_output(&mut self, crop_output: CropOutput) -> &mut Self {
+        self.crop_output = crop_output;
+        self
+    }
+
+    /// Appends `effect` to the post-processing chain.
+    #[inline]
+    pub fn post_effect(&mut self, effect: PostEffect) -> &mut Self {
+        self.post_effects.push(effect);
+        self
+    }
+
+    /// Exposure adjustment in stops (EV).
+    #[inline]
+    pub fn exposure(&mut self, exposure: f64) -> &mut Self {
+        self.tone_mapping.exposure = exposure;
+        self
+    }
+
+    /// Per-channel gains applied before tone mapping, see [`crate::tonemap::white_balance`].
+    #[inline]
+    pub fn white_balance(&mut self, white_balance: Color) -> &mut Self {
+        self.tone_mapping.white_balance = white_balance;
+        self
+    }
+
+    #[inline]
+    pub fn tone_mapper(&mut self, tone_mapper: ToneMapper) -> &mut Self {
+        self.tone_mapping.tone_mapper = tone_mapper;
+        self
+    }
+
+    /// Renders in `working_space`. Scene colors, including the background, are given in linear
+    /// sRGB or decoded from sRGB images and vertex colors, and are converted as light picks them
+    /// up; the film keeps the result in this space until it is written.
+    #[inline]
+    pub fn working_space(&mut self, working_space: ColorSpace) -> &mut Self {
+        self.working_space = working_space;
+        self
+    }
+
+    #[inline]
+    pub fn output_format(&mut self, output_format: OutputFormat) -> &mut Self {
+        self.output_format = Some(output_format);
+        self
+    }
+
+    #[inline]
+    pub fn background(&mut self, background: Color) -> &mut Self {
+        self.background = background;
+        self
+    }
+
+    /// Limits the radiance a sample receives from light reached after a single bounce. Removes
+    /// fireflies at the cost of darkening small, bright lights.
+    #[inline]
+    pub fn clamp_direct(&mut self, limit: f64) -> &mut Self {
+        self.clamp_direct = Some(limit);
+        self
+    }
+
+    /// Limits the radiance a sample receives from light reached after two or more bounces.
+    #[inline]
+    pub fn clamp_indirect(&mut self, limit: f64) -> &mut Self {
+        self.clamp_indirect = Some(limit);
+        self
+    }
+
+    pub(crate) fn crop_window(&self) -> CropWindow {
+        let full = CropWindow::new(0, 0, self.image_width, self.image_height);
+        let crop = match self.crop {
+            Some(crop) => crop,
+            None => return full,
+        };
+
+        let x = crop.x.min(self.image_width);
+        let y = crop.y.min(self.image_height);
+        let width = crop.width.min(self.image_width - x);
+        let height = crop.height.min(self.image_height - y);
+
+        CropWindow::new(x, y, width, height)
+    }
+
+    pub fn build(&self) -> Camera {
+        let aspect_ratio = (self.image_width as f64) / (self.image_height as f64);
+        let center = self.lookfrom;
+
+        let w = (self.lookfrom - self.lookat).normalize();
+        let u = self.vup.cross(w).normalize();
+        let v = w.cross(u);
+
+        let focus_dist = match self.focus {
+            Focus::LookAt => self.lookat.distance(self.lookfrom),
+            Focus::Distance(distance) => distance,
+            Focus::Point(point) => (self.lookfrom - point).dot(w),
+        };
+        let (viewport_width, viewport_height) = match self.projection {
+            Projection::Orthographic { view_width } => (view_width, view_width / aspect_ratio),
+            _ => {
+                let viewport_height = 2. * focus_dist * (self.vfov.to_radians() / 2.).tan();
+                (aspect_ratio * viewport_height, viewport_height)
+            }
+        };
+
+        let viewport_u = viewport_width * u;
+        let viewport_v = -viewport_height * v;
+
+        let pixel_delta_u = viewport_u / (self.image_width as f64);
+        let pixel_delta_v = viewport_v / (self.image_height as f64);
+
+        let focus_offset = focus_dist * w;
+        let viewport_upper_left = center - focus_offset - 0.5 * (viewport_u + viewport_v);
+        let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+        let film_distance = match &self.projection {
+            Projection::Realistic(lens) => lens.focus(focus_dist),
+            _ => 0.,
+        };
+
+        let defocus_radius = focus_dist * (self.defocus_angle.to_radians() / 2.).tan();
+        let defocus_disk_u = defocus_radius * u;
+        let defocus_disk_v = defocus_radius * v;
+
+        Camera {
+            image_width: self.image_width,
+            image_height: self.image_height,
+            samples_per_pixel: self.samples_per_pixel,
+            max_depth: self.max_depth,
+            background: self.background,
+            clamp: SampleClamp {
+                direct: self.clamp_direct,
+                indirect: self.clamp_indirect,
+            },
+
+            samples_per_pass: self.samples_per_pass.max(1),
+            time_budget: self.time_budget,
+            noise_target: self.noise_target,
+            outlier_rejection: self.outlier_rejection,
+
+            projection: self.projection.clone(),
+            center,
+            basis: [u, v, w],
+            focus_offset,
+            film_distance,
+            pixel_delta_u,
+            pixel_delta_v,
+            pixel00_loc,
+
+            defocus_disk_u,
+            defocus_disk_v,
+            aperture: self.aperture.clone(),
+
+            shutter_open: self.shutter_open,
+            shutter_close: self.shutter_close,
+
+            crop: self.crop_window(),
+            crop_output: self.crop_output,
+
+            post_effects: self.post_effects.clone(),
+            tone_mapping: self.tone_mapping,
+            working_space: self.working_space,
+            output_format: self.output_format,
+        }
+    }
+}
+
+// Only what a worker needs to fill a film is sent. Time budgets and noise targets are left out
+// because distributed jobs render a fixed number of samples, and post effects, tone mapping and
+// the output format are applied by whoever saves the merged film.
+impl Encode for CameraBuilder {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.image_width)
+            .put(&self.image_height)
+            .put(&self.samples_per_pixel)
+            .put(&self.max_depth)
+            .put(&self.background)
+            .put(&self.clamp_direct)
+            .put(&self.clamp_indirect)
+            .put(&self.samples_per_pass)
+            .put(&self.outlier_rejection)
+            .put(&self.projection)
+            .put(&self.vfov)
+            .put(&self.lookat)
+            .put(&self.lookfrom)
+            .put(&self.vup)
+            .put(&self.defocus_angle)
+            .put(&self.focus)
+            .put(&self.aperture)
+            .put(&self.shutter_open)
+            .put(&self.shutter_close)
+            .put(&self.crop)
+            .put(&self.working_space);
+    }
+}
+
+impl Decode for CameraBuilder {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            image_width: decoder.get()?,
+            image_height: decoder.get()?,
+            samples_per_pixel: decoder.get()?,
+            max_depth: decoder.get()?,
+            background: decoder.get()?,
+            clamp_direct: decoder.get()?,
+            clamp_indirect: decoder.get()?,
+            samples_per_pass: decoder.get()?,
+            outlier_rejection: decoder.get()?,
+            projection: decoder.get()?,
+            vfov: decoder.get()?,
+            lookat: decoder.get()?,
+            lookfrom: decoder.get()?,
+            vup: decoder.get()?,
+            defocus_angle: decoder.get()?,
+            focus: decoder.get()?,
+            aperture: decoder.get()?,
+            shutter_open: decoder.get()?,
+            shutter_close: decoder.get()?,
+            crop: decoder.get()?,
+            working_space: decoder.get()?,
+            ..Default::default()
+        })
+    }
+}
+
+impl Encode for Projection {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Self::Perspective => encoder.put(&0u8),
+            Self::Orthographic { view_width } => encoder.put(&1u8).put(view_width),
+            Self::Equirectangular => encoder.put(&2u8),
+            Self::Cubemap => encoder.put(&3u8),
+            Self::Fisheye { fov, mapping } => encoder.put(&4u8).put(fov).put(mapping),
+            Self::Realistic(lens) => encoder.put(&5u8).put(lens),
+        };
+    }
+}
+
+impl Decode for Projection {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::Perspective,
+            1 => Self::Orthographic {
+                view_width: decoder.get()?,
+            },
+            2 => Self::Equirectangular,
+            3 => Self::Cubemap,
+            4 => Self::Fisheye {
+                fov: decoder.get()?,
+                mapping: decoder.get()?,
+            },
+            5 => Self::Realistic(decoder.get()?),
+            tag => return Err(unknown_tag("projection", tag)),
+        })
+    }
+}
+
+impl Encode for Focus {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Self::LookAt => encoder.put(&0u8),
+            Self::Distance(distance) => encoder.put(&1u8).put(distance),
+            Self::Point(point) => encoder.put(&2u8).put(point),
+        };
+    }
+}
+
+impl Decode for Focus {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::LookAt,
+            1 => Self::Distance(decoder.get()?),
+            2 => Self::Point(decoder.get()?),
+            tag => return Err(unknown_tag("focus", tag)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hittable::{HittableList, Quad, Sphere},
+        material::Material,
+        progress::NoProgress,
+    };
+    use cgmath::Vector2;
+
+    fn render(builder: &CameraBuilder) -> Film {
+        render_world(builder, &HittableList::new()).0
+    }
+
+    fn render_world<H: Hittable + Sync>(builder: &CameraBuilder, world: &H) -> (Film, RenderStats) {
+        let mut options = RenderOptions::default();
+        options.progress(NoProgress);
+        builder.build().render_film_with(world, &options)
+    }
+
+    fn small() -> CameraBuilder {
+        let mut builder = CameraBuilder::default();
+        builder.image_width(4).image_height(3).samples_per_pass(4);
+        builder
+    }
+
+    #[test]
+    fn renders_samples_per_pixel_in_passes() {
+        let film = render(small().samples_per_pixel(10));
+        assert_eq!(film.samples(0, 0), 10);
+        assert_eq!(film.samples(3, 2), 10);
+    }
+
+    #[test]
+    fn unreachable_noise_target_stops_at_samples_per_pixel() {
+        // Not even a noiseless image gets below zero.
+        let film = render(small().samples_per_pixel(6).noise_target(-1.));
+        assert_eq!(film.samples(0, 0), 6);
+    }
+
+    #[test]
+    fn reached_noise_target_stops_early() {
+        // A constant background has no noise at all.
+        let film = render(small().samples_per_pixel(100).noise_target(0.1));
+        assert_eq!(film.samples(0, 0), 4);
+    }
+
+    #[test]
+    fn time_budget_goes_past_default_samples_per_pixel() {
+        let start = Instant::now();
+        let film = render(small().time_budget(Duration::from_millis(200)));
+        assert!(film.samples(0, 0) > DEFAULT_SAMPLES_PER_PIXEL);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn unbudgeted_render_takes_default_samples_per_pixel() {
+        let film = render(&small());
+        assert_eq!(film.samples(0, 0), DEFAULT_SAMPLES_PER_PIXEL);
+    }
+
+    #[test]
+    fn spent_time_budget_stops_after_one_pass() {
+        let film = render(small().samples_per_pixel(100).time_budget(Duration::ZERO));
+        assert_eq!(film.samples(0, 0), 4);
+    }
+
+    #[test]
+    fn empty_crop_window_finishes() {
+        let film = render(
+            small()
+                .samples_per_pixel(u32::MAX)
+                .noise_target(0.)
+                .crop(CropWindow::new(4, 0, 2, 2)),
+        );
+        assert_eq!(film.width(), 0);
+        assert_eq!(film.noise(), 0.);
+    }
+
+    #[test]
+    fn concurrent_renders_count_their_own_work() {
+        let gray = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let mut spheres = HittableList::new();
+        spheres.push(Sphere::new(Point3::origin(), 0.5, gray.clone()));
+        let mut quads = HittableList::new();
+        quads.push(Quad::new(
+            Point3::new(-1., -1., 0.),
+            2. * Vector3::unit_x(),
+            2. * Vector3::unit_y(),
+            gray,
+        ));
+
+        let mut builder = small();
+        builder.samples_per_pixel(8);
+        let (sphere_stats, quad_stats) = std::thread::scope(|scope| {
+            let spheres = scope.spawn(|| render_world(&builder, &spheres).1);
+            let quads = scope.spawn(|| render_world(&builder, &quads).1);
+            (spheres.join().unwrap(), quads.join().unwrap())
+        });
+
+        for stats in [sphere_stats, quad_stats] {
+            assert_eq!(stats.camera_rays, 4 * 3 * 8);
+        }
+        assert!(sphere_stats.sphere_tests > 0);
+        assert_eq!(sphere_stats.quad_tests, 0);
+        assert!(quad_stats.quad_tests > 0);
+        assert_eq!(quad_stats.sphere_tests, 0);
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel_and_span_the_view_width() {
+        let view_width = 4.;
+        let lookfrom = Point3::new(1., 2., 3.);
+        let lookat = Point3::new(-1., 0., 2.);
+        let mut builder = CameraBuilder::default();
+        builder
+            .image_width(8)
+            .image_height(4)
+            .lookfrom(lookfrom)
+            .lookat(lookat)
+            .projection(Projection::Orthographic { view_width });
+        let camera = builder.build();
+        let view = (lookat - lookfrom).normalize();
+        let [u, v, _] = camera.basis;
+
+        let (mut min, mut max) = (
+            Vector2::from_value(f64::INFINITY),
+            Vector2::from_value(f64::NEG_INFINITY),
+        );
+        for j in 0..4 {
+            for i in 0..8 {
+                for _ in 0..16 {
+                    let ray = camera.get_ray(i, j).unwrap();
+                    assert!((ray.direction.normalize() - view).magnitude() < 1e-12);
+
+                    let offset = ray.origin - lookfrom;
+                    assert!(offset.dot(view).abs() < 1e-12);
+                    let offset = Vector2::new(offset.dot(u), offset.dot(v));
+                    min = Vector2::new(min.x.min(offset.x), min.y.min(offset.y));
+                    max = Vector2::new(max.x.max(offset.x), max.y.max(offset.y));
+                }
+            }
+        }
+
+        // Jittered samples reach to within a pixel of the view's edges, and never past them.
+        let view = Vector2::new(view_width, view_width / 2.);
+        let pixel = view_width / 8.;
+        for axis in 0..2 {
+            assert!(min[axis] >= -view[axis] / 2. - 1e-12);
+            assert!(max[axis] <= view[axis] / 2. + 1e-12);
+            assert!(max[axis] - min[axis] > view[axis] - 2. * pixel);
+        }
+    }
+
+    #[test]
+    fn renders_in_the_working_space() {
+        let mut builder = small();
+        builder
+            .samples_per_pixel(1)
+            .background(Color::new(0.2, 0.4, 0.6))
+            .working_space(ColorSpace::AcesCg);
+        let film = render(&builder);
+        let expected = ColorSpace::AcesCg.from_linear_srgb(builder.background);
+        assert!(film.working_space() == ColorSpace::AcesCg);
+        assert!((film.color(1, 1) - expected).magnitude() < 1e-12);
+
+        // The camera sits inside the light, so every ray sees it.
+        let emit = Color::new(1., 0.5, 0.25);
+        let mut light = HittableList::new();
+        light.push(Sphere::new(
+            Point3::origin(),
+            100.,
+            Material::diffuse_light(Arc::new(emit)),
+        ));
+        let film = render_world(&builder, &light).0;
+        let expected = ColorSpace::AcesCg.from_linear_srgb(emit);
+        assert!((film.color(1, 1) - expected).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn crops_stitch_into_the_full_frame() {
+        let full = render(small().samples_per_pixel(4));
+
+        let mut stitched = Film::new(CropWindow::new(0, 0, 4, 3), 4, 3, full.working_space());
+        stitched.merge(&render(
+            small()
+                .samples_per_pixel(4)
+                .crop(CropWindow::new(0, 0, 3, 3)),
+        ));
+        stitched.merge(&render(
+            small()
+                .samples_per_pixel(8)
+                .crop(CropWindow::new(3, 0, 1, 3)),
+        ));
+
+        for j in 0..3 {
+            for i in 0..4 {
+                assert_eq!(stitched.samples(i, j), if i < 3 { 4 } else { 8 });
+                assert!((stitched.color(i, j) - full.color(i, j)).magnitude() < 1e-12);
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use crate::progress::{IndicatifProgress, ProgressObserver};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Settings of a single render that are not part of the camera.
+#[derive(Clone)]
+pub struct RenderOptions {
+    pub progress: Arc<dyn ProgressObserver>,
+    pub cancellation: CancellationToken,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            progress: Arc::new(IndicatifProgress::new()),
+            cancellation: CancellationToken::new(),
+        }
+    }
+}
+
+impl RenderOptions {
+    #[inline]
+    pub fn progress<P: ProgressObserver + 'static>(&mut self, progress: P) -> &mut Self {
+        self.progress = Arc::new(progress);
+        self
+    }
+
+    #[inline]
+    pub fn cancellation(&mut self, cancellation: CancellationToken) -> &mut Self {
+        self.cancellation = cancellation;
+        self
+    }
+}
+
+/// Shared flag to stop a render from another thread.
+///
+/// Render threads check the token between pixels, so a cancelled render stops shortly after and
+/// returns the samples taken so far. Clones of a token share its state.
+#[derive(Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
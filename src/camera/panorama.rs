@@ -0,0 +1,199 @@
+use crate::distributed::wire::{unknown_tag, Decode, Decoder, Encode, Encoder};
+use cgmath::Vector3;
+use std::{f64::consts::PI, io};
+
+#[derive(Clone, Copy)]
+pub enum FisheyeMapping {
+    /// Image radius proportional to the angle from the optical axis.
+    Equidistant,
+    /// Equal-area mapping: image radius proportional to `sin(theta / 2)`.
+    Equisolid,
+}
+
+/// Direction through the normalized image position `(s, t)` of a lat-long panorama, in camera
+/// space (x right, y up, z forward). The image center looks forward.
+pub(crate) fn equirectangular(s: f64, t: f64) -> Vector3<f64> {
+    let phi = (s - 0.5) * 2. * PI;
+    let theta = (0.5 - t) * PI;
+
+    Vector3::new(
+        theta.cos() * phi.sin(),
+        theta.sin(),
+        theta.cos() * phi.cos(),
+    )
+}
+
+/// Direction through the normalized image position `(s, t)` of a cubemap laid out as a 3x2 grid
+/// of square faces, in camera space:
+///
+/// ```text
+/// +X -X +Y
+/// -Y +Z -Z
+/// ```
+pub(crate) fn cubemap(s: f64, t: f64) -> Vector3<f64> {
+    let x = (3. * s).clamp(0., 2.999_999);
+    let y = (2. * t).clamp(0., 1.999_999);
+
+    let a = 2. * x.fract() - 1.;
+    let b = 1. - 2. * y.fract();
+
+    match (x as u32, y as u32) {
+        (0, 0) => Vector3::new(1., b, -a),
+        (1, 0) => Vector3::new(-1., b, a),
+        (2, 0) => Vector3::new(a, 1., -b),
+        (0, 1) => Vector3::new(a, -1., b),
+        (1, 1) => Vector3::new(a, b, 1.),
+        _ => Vector3::new(-a, b, -1.),
+    }
+}
+
+/// Direction through the point `(x, y)` of a fisheye image circle, in camera space. `(x, y)` is
+/// relative to the circle center and normalized by its radius; points outside the circle have no
+/// direction.
+pub(crate) fn fisheye(x: f64, y: f64, fov: f64, mapping: FisheyeMapping) -> Option<Vector3<f64>> {
+    let r = x.hypot(y);
+    if r > 1. {
+        return None;
+    }
+
+    let theta_max = 0.5 * fov.to_radians();
+    let theta = match mapping {
+        FisheyeMapping::Equidistant => r * theta_max,
+        FisheyeMapping::Equisolid => 2. * (r * (0.5 * theta_max).sin()).asin(),
+    };
+    let phi = y.atan2(x);
+
+    Some(Vector3::new(
+        theta.sin() * phi.cos(),
+        theta.sin() * phi.sin(),
+        theta.cos(),
+    ))
+}
+
+impl Encode for FisheyeMapping {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&(*self as u8));
+    }
+}
+
+impl Decode for FisheyeMapping {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::Equidistant,
+            1 => Self::Equisolid,
+            tag => return Err(unknown_tag("fisheye mapping", tag)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::prelude::*;
+
+    const TOLERANCE: f64 = 1e-9;
+
+    fn assert_direction(direction: Vector3<f64>, expected: Vector3<f64>) {
+        let direction = direction.normalize();
+        assert!(
+            (direction - expected).magnitude() < TOLERANCE,
+            "{:?} != {:?}",
+            direction,
+            expected
+        );
+    }
+
+    /// Image position of `direction` in a lat-long panorama.
+    fn equirectangular_position(direction: Vector3<f64>) -> (f64, f64) {
+        let direction = direction.normalize();
+        let phi = direction.x.atan2(direction.z);
+        let theta = direction.y.asin();
+
+        (phi / (2. * PI) + 0.5, 0.5 - theta / PI)
+    }
+
+    /// Image position of `direction` in a cubemap.
+    fn cubemap_position(d: Vector3<f64>) -> (f64, f64) {
+        let ((column, row), a, b) = if d.x.abs() >= d.y.abs().max(d.z.abs()) {
+            if d.x > 0. {
+                ((0, 0), -d.z / d.x, d.y / d.x)
+            } else {
+                ((1, 0), -d.z / d.x, -d.y / d.x)
+            }
+        } else if d.y.abs() >= d.z.abs() {
+            if d.y > 0. {
+                ((2, 0), d.x / d.y, -d.z / d.y)
+            } else {
+                ((0, 1), -d.x / d.y, -d.z / d.y)
+            }
+        } else if d.z > 0. {
+            ((1, 1), d.x / d.z, d.y / d.z)
+        } else {
+            ((2, 1), d.x / d.z, -d.y / d.z)
+        };
+
+        (
+            (column as f64 + 0.5 * (a + 1.)) / 3.,
+            (row as f64 + 0.5 * (1. - b)) / 2.,
+        )
+    }
+
+    #[test]
+    fn equirectangular_maps_centers_and_poles() {
+        assert_direction(equirectangular(0.5, 0.5), Vector3::unit_z());
+        assert_direction(equirectangular(0.75, 0.5), Vector3::unit_x());
+        assert_direction(equirectangular(0.25, 0.5), -Vector3::unit_x());
+        assert_direction(equirectangular(0., 0.5), -Vector3::unit_z());
+
+        // The top and bottom rows collapse into the poles.
+        for s in [0., 0.3, 0.5, 1.] {
+            assert_direction(equirectangular(s, 0.), Vector3::unit_y());
+            assert_direction(equirectangular(s, 1.), -Vector3::unit_y());
+        }
+    }
+
+    #[test]
+    fn cubemap_maps_face_centers() {
+        let faces = [
+            ((0, 0), Vector3::unit_x()),
+            ((1, 0), -Vector3::unit_x()),
+            ((2, 0), Vector3::unit_y()),
+            ((0, 1), -Vector3::unit_y()),
+            ((1, 1), Vector3::unit_z()),
+            ((2, 1), -Vector3::unit_z()),
+        ];
+        for ((column, row), axis) in faces {
+            let s = (column as f64 + 0.5) / 3.;
+            let t = (row as f64 + 0.5) / 2.;
+            assert_direction(cubemap(s, t), axis);
+            let (s_back, t_back) = cubemap_position(axis);
+            assert!((s_back - s).abs() < TOLERANCE && (t_back - t).abs() < TOLERANCE);
+        }
+    }
+
+    #[test]
+    fn positions_round_trip_through_directions() {
+        for k in 0..24 {
+            for l in 0..12 {
+                let (s, t) = ((k as f64 + 0.5) / 24., (l as f64 + 0.5) / 12.);
+                for (map, position) in [
+                    (
+                        equirectangular as fn(f64, f64) -> Vector3<f64>,
+                        equirectangular_position as fn(Vector3<f64>) -> (f64, f64),
+                    ),
+                    (cubemap, cubemap_position),
+                ] {
+                    let (s_back, t_back) = position(map(s, t));
+                    assert!(
+                        (s_back - s).abs() < TOLERANCE && (t_back - t).abs() < TOLERANCE,
+                        "({}, {}) came back as ({}, {})",
+                        s,
+                        t,
+                        s_back,
+                        t_back
+                    );
+                }
+            }
+        }
+    }
+}
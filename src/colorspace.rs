@@ -0,0 +1,190 @@
+use crate::{
+    distributed::wire::{unknown_tag, Decode, Decoder, Encode, Encoder},
+    Color,
+};
+use std::io;
+
+/// Linear RGB space, such as the one light transport is computed in, see
+/// [`CameraBuilder::working_space`](crate::camera::CameraBuilder::working_space).
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// Rec. 709 primaries with a D65 white point, shared with sRGB.
+    #[default]
+    Rec709,
+    /// ACES AP1 primaries with the ACES white point.
+    AcesCg,
+    /// Rec. 2020 primaries with a D65 white point.
+    Rec2020,
+}
+
+impl ColorSpace {
+    fn to_rec709(self) -> [[f64; 3]; 3] {
+        match self {
+            Self::Rec709 => IDENTITY,
+            Self::AcesCg => [
+                [1.7050510, -0.6217921, -0.0832588],
+                [-0.1302564, 1.1408048, -0.0105485],
+                [-0.0240034, -0.1289690, 1.1529724],
+            ],
+            Self::Rec2020 => [
+                [1.6604910, -0.5876411, -0.0728499],
+                [-0.1245505, 1.1328999, -0.0083494],
+                [-0.0181508, -0.1005789, 1.1187297],
+            ],
+        }
+    }
+
+    fn rec709_to(self) -> [[f64; 3]; 3] {
+        match self {
+            Self::Rec709 => IDENTITY,
+            Self::AcesCg => [
+                [0.6130973, 0.3395229, 0.0473793],
+                [0.0701942, 0.9163556, 0.0134526],
+                [0.0206156, 0.1095698, 0.8698151],
+            ],
+            Self::Rec2020 => [
+                [0.6274040, 0.3292820, 0.0433136],
+                [0.0690970, 0.9195400, 0.0113612],
+                [0.0163916, 0.0880132, 0.8955950],
+            ],
+        }
+    }
+
+    /// CIE xy chromaticities of the red, green and blue primaries and of the white point.
+    pub fn chromaticities(self) -> [[f64; 2]; 4] {
+        match self {
+            Self::Rec709 => [[0.64, 0.33], [0.30, 0.60], [0.15, 0.06], [0.3127, 0.3290]],
+            Self::AcesCg => [
+                [0.713, 0.293],
+                [0.165, 0.830],
+                [0.128, 0.044],
+                [0.32168, 0.33767],
+            ],
+            Self::Rec2020 => [
+                [0.708, 0.292],
+                [0.170, 0.797],
+                [0.131, 0.046],
+                [0.3127, 0.3290],
+            ],
+        }
+    }
+
+    /// Converts a linear color of this space into the space `to`. Differing white points are
+    /// adapted with the Bradford transform.
+    pub fn convert(self, color: Color, to: ColorSpace) -> Color {
+        if self == to {
+            return color;
+        }
+
+        mul(to.rec709_to(), mul(self.to_rec709(), color))
+    }
+
+    /// Converts a linear sRGB color, e.g. a constant picked in a color picker, into this space.
+    pub fn from_linear_srgb(self, color: Color) -> Color {
+        ColorSpace::Rec709.convert(color, self)
+    }
+
+    /// Decodes an 8-bit sRGB color into a linear color of this space.
+    pub fn from_srgb8(self, rgb: [u8; 3]) -> Color {
+        let color = Color::new(
+            srgb_decode(rgb[0] as f64 / 255.),
+            srgb_decode(rgb[1] as f64 / 255.),
+            srgb_decode(rgb[2] as f64 / 255.),
+        );
+
+        self.from_linear_srgb(color)
+    }
+}
+
+const IDENTITY: [[f64; 3]; 3] = [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+
+pub(crate) fn mul(m: [[f64; 3]; 3], c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.x + m[0][1] * c.y + m[0][2] * c.z,
+        m[1][0] * c.x + m[1][1] * c.y + m[1][2] * c.z,
+        m[2][0] * c.x + m[2][1] * c.y + m[2][2] * c.z,
+    )
+}
+
+/// sRGB opto-electronic transfer function, from linear to encoded values.
+pub fn srgb_encode(linear: f64) -> f64 {
+    if linear <= 0.0031308 {
+        12.92 * linear
+    } else {
+        1.055 * linear.powf(2.4f64.recip()) - 0.055
+    }
+}
+
+/// Inverse of [`srgb_encode`].
+pub fn srgb_decode(encoded: f64) -> f64 {
+    if encoded <= 0.04045 {
+        encoded / 12.92
+    } else {
+        ((encoded + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl Encode for ColorSpace {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&(*self as u8));
+    }
+}
+
+impl Decode for ColorSpace {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::Rec709,
+            1 => Self::AcesCg,
+            2 => Self::Rec2020,
+            tag => return Err(unknown_tag("color space", tag)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::prelude::*;
+
+    // The matrices are given to seven digits.
+    const TOLERANCE: f64 = 1e-5;
+
+    const SPACES: [ColorSpace; 3] = [ColorSpace::Rec709, ColorSpace::AcesCg, ColorSpace::Rec2020];
+
+    #[test]
+    fn srgb_transfer_round_trips() {
+        for i in 0..=100 {
+            let linear = i as f64 / 100.;
+            assert!((srgb_decode(srgb_encode(linear)) - linear).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn conversions_round_trip() {
+        let color = Color::new(0.8, 0.3, 0.05);
+        for from in SPACES {
+            for to in SPACES {
+                let back = to.convert(from.convert(color, to), from);
+                assert!((back - color).magnitude() < TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn white_stays_white_between_d65_spaces() {
+        let white = Color::new(1., 1., 1.);
+        let converted = ColorSpace::Rec2020.from_linear_srgb(white);
+        assert!((converted - white).magnitude() < TOLERANCE);
+    }
+
+    #[test]
+    fn encoded_srgb_decodes_to_linear() {
+        assert_eq!(ColorSpace::Rec709.from_srgb8([0, 0, 0]), Color::zero());
+        assert_eq!(
+            ColorSpace::Rec709.from_srgb8([255, 255, 255]),
+            Color::new(1., 1., 1.)
+        );
+        let mid = ColorSpace::Rec709.from_srgb8([188, 188, 188]).x;
+        assert!((mid - 0.5).abs() < 0.01);
+    }
+}
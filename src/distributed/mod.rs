@@ -0,0 +1,475 @@
+//! Rendering a frame across processes, possibly on different hosts, over TCP.
+//!
+//! A [`Coordinator`] sends the scene to every connected worker, hands out jobs covering parts of
+//! the frame and merges the films the workers send back. Workers are started separately, e.g.
+//! as processes of the same program, and call [`run_worker`] with the coordinator's address.
+//!
+//! Messages are frames of a `u64` length followed by a tag and the encoded payload. There is no
+//! authentication, so the port must not be reachable from untrusted networks.
+
+pub(crate) mod wire;
+
+use crate::{
+    camera::{CameraBuilder, RenderOptions, DEFAULT_SAMPLES_PER_PIXEL},
+    film::{CropWindow, Film},
+    hittable::{decode_object, Hittable, HittableList},
+    progress::NoProgress,
+    stats::RenderStats,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use wire::{invalid_data, unknown_tag, Decoder, Encoder};
+
+/// Largest frame sent or accepted, to fail on garbage instead of trying to allocate it.
+const MAX_FRAME: u64 = 1 << 30;
+
+/// How long [`Coordinator::render`] waits for workers to connect by default.
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long a worker may take to send back a job by default.
+const JOB_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Interval between polls of the listener for workers connecting.
+const ACCEPT_POLL: Duration = Duration::from_millis(10);
+
+mod tag {
+    pub(super) const SCENE: u8 = 0;
+    pub(super) const JOB: u8 = 1;
+    pub(super) const FINISH: u8 = 2;
+    pub(super) const FILM: u8 = 3;
+}
+
+/// How the frame is split into jobs.
+#[derive(Clone, Copy)]
+pub enum Partition {
+    /// Square tiles of `size` pixels, each rendered with all samples.
+    Tiles(u32),
+    /// The whole frame, rendered in jobs of at most `samples` samples per pixel.
+    Samples(u32),
+}
+
+#[derive(Clone, Copy)]
+struct Job {
+    window: CropWindow,
+    samples: u32,
+}
+
+impl Partition {
+    fn jobs(self, window: CropWindow, samples: u32) -> VecDeque<Job> {
+        match self {
+            Self::Tiles(size) => {
+                let size = size.max(1);
+                let mut jobs = VecDeque::new();
+                for y in (window.y..window.y + window.height).step_by(size as usize) {
+                    for x in (window.x..window.x + window.width).step_by(size as usize) {
+                        let width = size.min(window.x + window.width - x);
+                        let height = size.min(window.y + window.height - y);
+                        jobs.push_back(Job {
+                            window: CropWindow::new(x, y, width, height),
+                            samples,
+                        });
+                    }
+                }
+
+                jobs
+            }
+            Self::Samples(per_job) => {
+                let per_job = per_job.max(1);
+                (0..samples)
+                    .step_by(per_job as usize)
+                    .map(|start| Job {
+                        window,
+                        samples: per_job.min(samples - start),
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Jobs waiting for a worker, and the number of jobs being rendered, which may come back if their
+/// worker fails.
+struct Queue {
+    pending: VecDeque<Job>,
+    in_flight: usize,
+}
+
+struct Scheduler {
+    queue: Mutex<Queue>,
+    changed: Condvar,
+}
+
+impl Scheduler {
+    /// Next job to render, or `None` once all jobs are done.
+    fn next(&self) -> Option<Job> {
+        let mut queue = self.queue.lock().unwrap();
+        loop {
+            if let Some(job) = queue.pending.pop_front() {
+                queue.in_flight += 1;
+                return Some(job);
+            }
+            if queue.in_flight == 0 {
+                return None;
+            }
+            queue = self.changed.wait(queue).unwrap();
+        }
+    }
+
+    fn done(&self) {
+        self.queue.lock().unwrap().in_flight -= 1;
+        self.changed.notify_all();
+    }
+
+    fn has_pending(&self) -> bool {
+        !self.queue.lock().unwrap().pending.is_empty()
+    }
+
+    /// Puts the job of a failed worker back for the others.
+    fn retry(&self, job: Job) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.in_flight -= 1;
+        queue.pending.push_front(job);
+        self.changed.notify_all();
+    }
+}
+
+/// Listening end of a distributed render.
+pub struct Coordinator {
+    listener: TcpListener,
+    accept_timeout: Duration,
+    job_timeout: Duration,
+}
+
+impl Coordinator {
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        // Polled, as std has no timeout for accepting connections.
+        listener.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener,
+            accept_timeout: ACCEPT_TIMEOUT,
+            job_timeout: JOB_TIMEOUT,
+        })
+    }
+
+    /// Sets how long a render waits for its workers to connect, one minute by default. Workers
+    /// that connect later, or once no jobs are left to hand out, are left out, and the render
+    /// fails if none connected.
+    pub fn accept_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.accept_timeout = timeout;
+        self
+    }
+
+    /// Sets how long a worker may take to send back a job, ten minutes by default. A worker that
+    /// takes longer is dropped and its job handed to the others.
+    pub fn job_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.job_timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Renders `world` as seen by `camera` on `workers` workers, waiting for them to connect up to
+    /// the [accept timeout](Self::accept_timeout). Jobs of a worker that fails are handed to the
+    /// remaining ones.
+    ///
+    /// Every job renders a fixed number of samples, so the time budget and noise target of the
+    /// camera are ignored. Post effects, tone mapping and the output format are left to saving
+    /// the returned film.
+    #[allow(private_bounds)]
+    pub fn render<H: Hittable>(
+        &self,
+        camera: &CameraBuilder,
+        world: &H,
+        workers: usize,
+        partition: Partition,
+    ) -> io::Result<(Film, RenderStats)> {
+        let start = Instant::now();
+
+        let mut encoder = Encoder::new();
+        encoder.put(&tag::SCENE).put(camera);
+        world.encode_object(&mut encoder);
+        let scene = encoder.finish();
+
+        let window = camera.crop_window();
+        let scheduler = Scheduler {
+            queue: Mutex::new(Queue {
+                pending: partition.jobs(
+                    window,
+                    camera
+                        .samples_per_pixel
+                        .unwrap_or(DEFAULT_SAMPLES_PER_PIXEL),
+                ),
+                in_flight: 0,
+            }),
+            changed: Condvar::new(),
+        };
+
+        let mut film = Film::new(
+            window,
+            camera.image_width,
+            camera.image_height,
+            camera.working_space,
+        );
+        if let Some(sigmas) = camera.outlier_rejection {
+            film.reject_outliers(sigmas);
+        }
+        let result = Mutex::new((film, RenderStats::default()));
+
+        let errors = thread::scope(|scope| -> io::Result<Vec<io::Error>> {
+            let deadline = Instant::now() + self.accept_timeout;
+            let mut handles = Vec::with_capacity(workers);
+            while handles.len() < workers {
+                let Some(stream) = self.accept(deadline, &scheduler)? else {
+                    break;
+                };
+                stream.set_read_timeout(Some(self.job_timeout))?;
+                stream.set_write_timeout(Some(self.job_timeout))?;
+                handles.push(scope.spawn(|| serve(stream, &scene, &scheduler, &result)));
+            }
+            if handles.is_empty() && scheduler.has_pending() {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "no worker connected in time",
+                ));
+            }
+
+            Ok(handles
+                .into_iter()
+                .filter_map(|handle| handle.join().unwrap().err())
+                .collect())
+        })?;
+
+        if !scheduler.queue.lock().unwrap().pending.is_empty() {
+            return Err(errors
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| io::Error::other("no workers to render the frame")));
+        }
+
+        let (film, mut stats) = result.into_inner().unwrap();
+        // Workers render at the same time, so their times add up to more than the render took.
+        stats.render = start.elapsed();
+
+        Ok((film, stats))
+    }
+
+    /// Next worker to connect, or `None` if none does before `deadline` or while `scheduler` still
+    /// has jobs to hand out.
+    fn accept(&self, deadline: Instant, scheduler: &Scheduler) -> io::Result<Option<TcpStream>> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    return Ok(Some(stream));
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                    if Instant::now() >= deadline || !scheduler.has_pending() {
+                        return Ok(None);
+                    }
+                    thread::sleep(ACCEPT_POLL);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Feeds jobs to one worker until none are left.
+fn serve(
+    mut stream: TcpStream,
+    scene: &[u8],
+    scheduler: &Scheduler,
+    result: &Mutex<(Film, RenderStats)>,
+) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    write_frame(&mut stream, scene)?;
+
+    while let Some(job) = scheduler.next() {
+        match render_job(&mut stream, job) {
+            Ok((film, stats)) => {
+                let mut result = result.lock().unwrap();
+                result.0.merge(&film);
+                result.1.merge(&stats);
+                drop(result);
+                scheduler.done();
+            }
+            Err(err) => {
+                scheduler.retry(job);
+                return Err(err);
+            }
+        }
+    }
+
+    let mut encoder = Encoder::new();
+    encoder.put(&tag::FINISH);
+    write_frame(&mut stream, &encoder.finish())
+}
+
+fn render_job(stream: &mut TcpStream, job: Job) -> io::Result<(Film, RenderStats)> {
+    let mut encoder = Encoder::new();
+    encoder.put(&tag::JOB).put(&job.window).put(&job.samples);
+    write_frame(stream, &encoder.finish())?;
+
+    let frame = read_frame(stream)?;
+    let mut decoder = Decoder::new(&frame);
+    match decoder.get()? {
+        tag::FILM => {}
+        tag => return Err(unknown_tag("worker message", tag)),
+    }
+    let film: Film = decoder.get()?;
+    if film.window() != job.window {
+        return Err(invalid_data("film does not cover the job"));
+    }
+
+    Ok((film, decoder.get()?))
+}
+
+/// Connects to the coordinator at `addr` and renders jobs until it is done.
+pub fn run_worker<A: ToSocketAddrs>(addr: A) -> io::Result<()> {
+    let mut stream = TcpStream::connect(addr)?;
+    stream.set_nodelay(true)?;
+
+    let frame = read_frame(&mut stream)?;
+    let mut decoder = Decoder::new(&frame);
+    match decoder.get()? {
+        tag::SCENE => {}
+        tag => return Err(unknown_tag("coordinator message", tag)),
+    }
+    let camera: CameraBuilder = decoder.get()?;
+    let world = decode_object(&mut decoder)?;
+    let world = HittableList {
+        aabb: world.bounding_box(),
+        objects: vec![world],
+    };
+
+    let mut options = RenderOptions::default();
+    options.progress(NoProgress);
+
+    loop {
+        let frame = read_frame(&mut stream)?;
+        let mut decoder = Decoder::new(&frame);
+        match decoder.get()? {
+            tag::JOB => {}
+            tag::FINISH => return Ok(()),
+            tag => return Err(unknown_tag("coordinator message", tag)),
+        }
+
+        let (film, stats) = camera
+            .clone()
+            .crop(decoder.get()?)
+            .samples_per_pixel(decoder.get()?)
+            .build()
+            .render_film_with(&world, &options);
+
+        let mut encoder = Encoder::new();
+        encoder.put(&tag::FILM).put(&film).put(&stats);
+        write_frame(&mut stream, &encoder.finish())?;
+    }
+}
+
+fn write_frame(stream: &mut TcpStream, frame: &[u8]) -> io::Result<()> {
+    if frame.len() as u64 > MAX_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "frame too large to send",
+        ));
+    }
+
+    stream.write_all(&(frame.len() as u64).to_le_bytes())?;
+    stream.write_all(frame)
+}
+
+fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut len = [0; 8];
+    stream.read_exact(&mut len)?;
+    let len = u64::from_le_bytes(len);
+    if len > MAX_FRAME {
+        return Err(invalid_data("frame too large"));
+    }
+
+    // Grown as the data arrives rather than allocated up front from the length.
+    let mut frame = Vec::new();
+    stream.take(len).read_to_end(&mut frame)?;
+    if frame.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(frame)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_without_workers_times_out() {
+        let mut coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        coordinator.accept_timeout(Duration::from_millis(50));
+
+        let err = coordinator
+            .render(
+                &CameraBuilder::default(),
+                &HittableList::new(),
+                1,
+                Partition::Tiles(16),
+            )
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+
+    fn small() -> CameraBuilder {
+        let mut camera = CameraBuilder::default();
+        camera.image_width(8).image_height(8).samples_per_pixel(2);
+        camera
+    }
+
+    #[test]
+    fn renders_without_waiting_for_missing_workers() {
+        let mut coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        coordinator.accept_timeout(Duration::from_secs(60));
+        let addr = coordinator.local_addr().unwrap();
+        let worker = thread::spawn(move || run_worker(addr));
+
+        let start = Instant::now();
+        let result = coordinator.render(&small(), &HittableList::new(), 2, Partition::Tiles(4));
+        worker.join().unwrap().unwrap();
+        let (film, _) = result.unwrap();
+        assert!(start.elapsed() < Duration::from_secs(30));
+        assert_eq!(film.samples(7, 7), 2);
+    }
+
+    #[test]
+    fn hands_job_of_hung_worker_to_another() {
+        let mut coordinator = Coordinator::bind("127.0.0.1:0").unwrap();
+        coordinator.job_timeout(Duration::from_millis(300));
+        let addr = coordinator.local_addr().unwrap();
+
+        // Takes the scene and a job, but never answers until the coordinator hangs up.
+        let hung = TcpStream::connect(addr).unwrap();
+        let hung = thread::spawn(move || io::copy(&mut &hung, &mut io::sink()));
+        let worker = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            run_worker(addr)
+        });
+
+        let (film, _) = coordinator
+            .render(&small(), &HittableList::new(), 2, Partition::Tiles(4))
+            .unwrap();
+        for (i, j) in [(0, 0), (7, 0), (0, 7), (7, 7)] {
+            assert_eq!(film.samples(i, j), 2);
+        }
+        worker.join().unwrap().unwrap();
+        hung.join().unwrap().unwrap();
+    }
+}
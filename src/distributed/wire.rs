@@ -0,0 +1,415 @@
+//! Compact little-endian binary encoding of scenes, cameras and films.
+//!
+//! Values are written field by field without any self-description, so both ends must run the
+//! same version of the crate. Trait objects and enums are prefixed with a one-byte tag. An `Arc`
+//! is written once per message, later copies refer back to it, so shared objects stay shared.
+
+use cgmath::{Matrix3, Point3, Quaternion, Vector2, Vector3};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    io,
+    sync::Arc,
+    time::Duration,
+};
+
+/// Deepest nesting of `Arc`s a message may have, so a hostile message cannot overflow
+/// the stack of the decoder.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+pub(crate) trait Encode {
+    fn encode(&self, encoder: &mut Encoder);
+}
+
+/// Value sent behind a trait object: writes its tag, then itself, for the [`Decode`] of the
+/// trait object.
+pub(crate) trait EncodeObject {
+    fn encode_object(&self, encoder: &mut Encoder);
+}
+
+pub(crate) trait Decode: Sized {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self>;
+}
+
+#[derive(Default)]
+pub(crate) struct Encoder {
+    buf: Vec<u8>,
+    /// Indices of the `Arc`s already written, by address and type.
+    shared: HashMap<(usize, TypeId), u32>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn put<T: Encode + ?Sized>(&mut self, value: &T) -> &mut Self {
+        value.encode(self);
+        self
+    }
+
+    pub(crate) fn bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(bytes);
+        self
+    }
+
+    /// Writes `value` with `encode` the first time, and its index every time after.
+    fn shared<T: ?Sized + 'static>(&mut self, value: &Arc<T>, encode: impl FnOnce(&mut Self)) {
+        let key = (
+            Arc::as_ptr(value) as *const () as usize,
+            TypeId::of::<Arc<T>>(),
+        );
+        if let Some(&index) = self.shared.get(&key) {
+            self.put(&true).put(&index);
+        } else {
+            self.put(&false);
+            encode(self);
+            // Numbered once written, like the decoder does, as the value may hold `Arc`s itself.
+            let index = self.shared.len() as u32;
+            self.shared.insert(key, index);
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub(crate) struct Decoder<'a> {
+    data: &'a [u8],
+    shared: Vec<Box<dyn Any>>,
+    depth: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            shared: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    /// Reads an `Arc` written by [`Encoder::shared`], decoding it with `decode` the first time.
+    pub(crate) fn shared<T: Clone + 'static>(
+        &mut self,
+        decode: impl FnOnce(&mut Self) -> io::Result<T>,
+    ) -> io::Result<T> {
+        if self.get()? {
+            let index: u32 = self.get()?;
+            return self
+                .shared
+                .get(index as usize)
+                .and_then(|value| value.downcast_ref::<T>())
+                .cloned()
+                .ok_or_else(|| invalid_data("reference to an unknown shared object"));
+        }
+
+        if self.depth == MAX_DEPTH {
+            return Err(invalid_data("objects nested too deeply"));
+        }
+
+        self.depth += 1;
+        let value = decode(self);
+        self.depth -= 1;
+        let value = value?;
+        self.shared.push(Box::new(value.clone()));
+        Ok(value)
+    }
+
+    pub(crate) fn get<T: Decode>(&mut self) -> io::Result<T> {
+        T::decode(self)
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.data.len() {
+            return Err(invalid_data("unexpected end of message"));
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_owned())
+}
+
+/// Error for an unknown one-byte tag of `what`.
+pub(crate) fn unknown_tag(what: &str, tag: u8) -> io::Error {
+    invalid_data(&format!("unknown {} tag {}", what, tag))
+}
+
+macro_rules! impl_number {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, encoder: &mut Encoder) {
+                    encoder.bytes(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+                    Ok(<$t>::from_le_bytes(decoder.array()?))
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(u8, u32, u64, f32, f64);
+
+impl Encode for bool {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&(*self as u8));
+    }
+}
+
+impl Decode for bool {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(decoder.get::<u8>()? != 0)
+    }
+}
+
+impl Encode for usize {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&(*self as u64));
+    }
+}
+
+impl Decode for usize {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(decoder.get::<u64>()? as usize)
+    }
+}
+
+impl Encode for Duration {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.as_secs_f64());
+    }
+}
+
+impl Decode for Duration {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Duration::from_secs_f64(decoder.get()?))
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Some(value) => encoder.put(&true).put(value),
+            None => encoder.put(&false),
+        };
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(if decoder.get()? {
+            Some(decoder.get()?)
+        } else {
+            None
+        })
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.len());
+        for value in self {
+            encoder.put(value);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(self.as_slice());
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let len: usize = decoder.get()?;
+        // Do not trust the length for the allocation, a bad message may claim anything. Reserving
+        // no more than the bytes left keeps it within the size of the message.
+        let capacity = len.min(decoder.data.len() / std::mem::size_of::<T>().max(1));
+        let mut values = Vec::with_capacity(capacity);
+        for _ in 0..len {
+            values.push(decoder.get()?);
+        }
+
+        Ok(values)
+    }
+}
+
+impl<T: Encode, const N: usize> Encode for [T; N] {
+    fn encode(&self, encoder: &mut Encoder) {
+        for value in self {
+            encoder.put(value);
+        }
+    }
+}
+
+impl<T: Decode, const N: usize> Decode for [T; N] {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let values: Vec<T> = (0..N).map(|_| decoder.get()).collect::<io::Result<_>>()?;
+
+        Ok(values.try_into().ok().unwrap())
+    }
+}
+
+impl<T: Encode + ?Sized + 'static> Encode for Arc<T> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.shared(self, |encoder| {
+            encoder.put(self.as_ref());
+        });
+    }
+}
+
+impl<T: Decode + 'static> Decode for Arc<T> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        decoder.shared(|decoder| Ok(Arc::new(decoder.get()?)))
+    }
+}
+
+impl Encode for Vector2<f64> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.x).put(&self.y);
+    }
+}
+
+impl Decode for Vector2<f64> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Vector2::new(decoder.get()?, decoder.get()?))
+    }
+}
+
+impl Encode for Vector3<f64> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.x).put(&self.y).put(&self.z);
+    }
+}
+
+impl Decode for Vector3<f64> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Vector3::new(decoder.get()?, decoder.get()?, decoder.get()?))
+    }
+}
+
+impl Encode for Point3<f64> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.x).put(&self.y).put(&self.z);
+    }
+}
+
+impl Decode for Point3<f64> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Point3::new(decoder.get()?, decoder.get()?, decoder.get()?))
+    }
+}
+
+impl Encode for Quaternion<f64> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.s).put(&self.v);
+    }
+}
+
+impl Decode for Quaternion<f64> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Quaternion::from_sv(decoder.get()?, decoder.get()?))
+    }
+}
+
+impl Encode for Matrix3<f64> {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.x).put(&self.y).put(&self.z);
+    }
+}
+
+impl Decode for Matrix3<f64> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Matrix3::from_cols(
+            decoder.get()?,
+            decoder.get()?,
+            decoder.get()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Encode + Decode>(value: &T) -> T {
+        let mut encoder = Encoder::new();
+        encoder.put(value);
+        let message = encoder.finish();
+
+        let mut decoder = Decoder::new(&message);
+        let decoded = decoder.get().unwrap();
+        assert!(decoder.data.is_empty());
+        decoded
+    }
+
+    #[test]
+    fn values_round_trip() {
+        assert_eq!(round_trip(&0xdead_beef_u32), 0xdead_beef);
+        assert_eq!(round_trip(&-1.5f64), -1.5);
+        assert!(round_trip(&true));
+        assert_eq!(round_trip(&usize::MAX), usize::MAX);
+        assert_eq!(
+            round_trip(&Duration::from_millis(1500)),
+            Duration::from_millis(1500)
+        );
+        assert_eq!(round_trip(&Some(7u8)), Some(7));
+        assert_eq!(round_trip(&None::<u8>), None);
+        assert_eq!(round_trip(&vec![1u64, 2, 3]), vec![1, 2, 3]);
+        assert_eq!(round_trip(&[0.25f32; 3]), [0.25; 3]);
+        assert_eq!(
+            round_trip(&Point3::new(1., -2., 3.)),
+            Point3::new(1., -2., 3.)
+        );
+        let rotation = Quaternion::new(0.5, -0.5, 0.5, -0.5);
+        assert_eq!(round_trip(&rotation), rotation);
+        let matrix = Matrix3::new(1., 2., 3., 4., 5., 6., 7., 8., 9.);
+        assert_eq!(round_trip(&matrix), matrix);
+    }
+
+    #[test]
+    fn shared_arcs_are_sent_once() {
+        let shared = Arc::new(vec![1u64; 100]);
+        let mut encoder = Encoder::new();
+        encoder.put(&vec![shared.clone(), shared.clone(), Arc::new(vec![2])]);
+        let message = encoder.finish();
+        // The second copy is a flag and an index rather than the values again.
+        assert!(message.len() < 2 * 100 * 8);
+
+        let decoded: Vec<Arc<Vec<u64>>> = Decoder::new(&message).get().unwrap();
+        assert!(Arc::ptr_eq(&decoded[0], &decoded[1]));
+        assert!(!Arc::ptr_eq(&decoded[0], &decoded[2]));
+        assert_eq!(*decoded[2], vec![2]);
+    }
+
+    #[test]
+    fn rejects_truncated_and_dangling_messages() {
+        let mut encoder = Encoder::new();
+        encoder.put(&vec![1u64, 2, 3]);
+        let message = encoder.finish();
+        let truncated = Decoder::new(&message[..message.len() - 1]).get::<Vec<u64>>();
+        assert!(truncated.is_err());
+
+        // A reference to an `Arc` that was never sent.
+        let mut encoder = Encoder::new();
+        encoder.put(&true).put(&0u32);
+        let message = encoder.finish();
+        assert!(Decoder::new(&message).get::<Arc<u64>>().is_err());
+    }
+}
@@ -0,0 +1,625 @@
+use crate::{
+    color_to_rgb,
+    colorspace::ColorSpace,
+    distributed::wire::{invalid_data, Decode, Decoder, Encode, Encoder},
+    luminance,
+    post::{FrameBuffer, PostEffect},
+    tonemap::ToneMapping,
+    Color,
+};
+use cgmath::{prelude::*, Vector2, Vector3};
+use image::RgbImage;
+use std::io;
+
+mod output;
+
+pub use output::{ExrPrecision, OutputFormat};
+
+/// Surface features of the first hit of a camera ray, accumulated for the AOVs.
+#[derive(Clone, Copy)]
+pub(crate) struct Features {
+    pub(crate) albedo: Color,
+    pub(crate) normal: Vector3<f64>,
+    /// Distance from the ray origin.
+    pub(crate) depth: f64,
+}
+
+impl Features {
+    fn zero() -> Self {
+        Self {
+            albedo: Color::zero(),
+            normal: Vector3::zero(),
+            depth: 0.,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(crate) struct Pixel {
+    sum: Color,
+    sum_sq: f64,
+    samples: u32,
+    /// Sum of the samples rejected as outliers, spread over the neighborhood when resolved.
+    outliers: Color,
+    outlier_samples: u32,
+
+    features: Features,
+    hits: u32,
+}
+
+impl Pixel {
+    fn new() -> Self {
+        Self {
+            sum: Color::zero(),
+            sum_sq: 0.,
+            samples: 0,
+            outliers: Color::zero(),
+            outlier_samples: 0,
+
+            features: Features::zero(),
+            hits: 0,
+        }
+    }
+
+    /// Adds a sample. With `outlier_rejection`, samples brighter than the mean by more than that
+    /// many standard deviations are set aside as outliers.
+    pub(crate) fn add_sample(&mut self, color: Color, outlier_rejection: Option<f64>) {
+        const MIN_SAMPLES: u32 = 8;
+
+        self.samples += 1;
+
+        let l = luminance(color);
+        let accepted = self.samples - 1 - self.outlier_samples;
+        if let Some(k) = outlier_rejection.filter(|_| accepted >= MIN_SAMPLES) {
+            let n = accepted as f64;
+            let mean = luminance(self.sum) / n;
+            let deviation = (self.sum_sq / n - mean * mean).max(0.).sqrt();
+            // Flat, converged pixels have no spread to compare against.
+            if l > mean + k * deviation.max(mean) {
+                self.outliers += color;
+                self.outlier_samples += 1;
+                return;
+            }
+        }
+
+        self.sum += color;
+        self.sum_sq += l * l;
+    }
+
+    pub(crate) fn add_features(&mut self, features: Features) {
+        self.features.albedo += features.albedo;
+        self.features.normal += features.normal;
+        self.features.depth += features.depth;
+        self.hits += 1;
+    }
+
+    fn merge(&mut self, other: &Pixel) {
+        self.sum += other.sum;
+        self.sum_sq += other.sum_sq;
+        self.samples += other.samples;
+        self.outliers += other.outliers;
+        self.outlier_samples += other.outlier_samples;
+
+        self.features.albedo += other.features.albedo;
+        self.features.normal += other.features.normal;
+        self.features.depth += other.features.depth;
+        self.hits += other.hits;
+    }
+
+    /// Mean of the samples, excluding the outliers.
+    fn color(&self) -> Color {
+        if self.samples == 0 {
+            Color::zero()
+        } else {
+            self.sum / (self.samples as f64)
+        }
+    }
+
+    /// Contribution of the outliers to the mean of the samples.
+    fn outlier_color(&self) -> Color {
+        if self.outlier_samples == 0 {
+            Color::zero()
+        } else {
+            self.outliers / (self.samples as f64)
+        }
+    }
+
+    /// Mean features over the samples that hit a surface. Pixels without hits have no albedo or
+    /// normal and an infinite depth.
+    fn features(&self) -> Features {
+        if self.hits == 0 {
+            return Features {
+                depth: f64::INFINITY,
+                ..Features::zero()
+            };
+        }
+
+        let n = self.hits as f64;
+        Features {
+            albedo: self.features.albedo / n,
+            normal: self.features.normal.normalize(),
+            depth: self.features.depth / n,
+        }
+    }
+
+    /// Standard error of the mean luminance, relative to the mean itself.
+    fn relative_error(&self) -> f64 {
+        let accepted = self.samples - self.outlier_samples;
+        if accepted < 2 {
+            return f64::INFINITY;
+        }
+
+        let n = accepted as f64;
+        let mean = luminance(self.sum) / n;
+        let variance = ((self.sum_sq / n - mean * mean) * n / (n - 1.)).max(0.);
+
+        (variance / n).sqrt() / (mean + 1e-2)
+    }
+}
+
+/// Rectangle of the image, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CropWindow {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl CropWindow {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[inline]
+    fn contains(&self, i: u32, j: u32) -> bool {
+        (self.x..self.x + self.width).contains(&i) && (self.y..self.y + self.height).contains(&j)
+    }
+}
+
+/// Floating-point framebuffer that accumulates radiance samples per pixel.
+///
+/// A film may cover only a window of the full image. Pixels are always addressed with
+/// coordinates of the full image, so films of different windows can be merged.
+#[derive(Clone)]
+pub struct Film {
+    window: CropWindow,
+    full_width: u32,
+    full_height: u32,
+    working_space: ColorSpace,
+    outlier_rejection: Option<f64>,
+    pub(crate) pixels: Vec<Pixel>,
+}
+
+impl Film {
+    /// Empty film covering `window` of a `full_width` x `full_height` image, accumulating colors
+    /// of `working_space`.
+    pub fn new(
+        window: CropWindow,
+        full_width: u32,
+        full_height: u32,
+        working_space: ColorSpace,
+    ) -> Self {
+        Self {
+            window,
+            full_width,
+            full_height,
+            working_space,
+            outlier_rejection: None,
+            pixels: vec![Pixel::new(); (window.width * window.height) as usize],
+        }
+    }
+
+    #[inline]
+    pub fn working_space(&self) -> ColorSpace {
+        self.working_space
+    }
+
+    /// Sets samples that are brighter than the mean of their pixel by more than `sigmas` standard
+    /// deviations aside as fireflies. Their energy is spread evenly over the 3x3 neighborhood of
+    /// the pixel instead of being lost.
+    pub fn reject_outliers(&mut self, sigmas: f64) {
+        self.outlier_rejection = Some(sigmas);
+    }
+
+    #[inline]
+    pub fn outlier_rejection(&self) -> Option<f64> {
+        self.outlier_rejection
+    }
+
+    #[inline]
+    pub fn width(&self) -> u32 {
+        self.window.width
+    }
+
+    #[inline]
+    pub fn height(&self) -> u32 {
+        self.window.height
+    }
+
+    #[inline]
+    pub fn window(&self) -> CropWindow {
+        self.window
+    }
+
+    #[inline]
+    pub fn full_width(&self) -> u32 {
+        self.full_width
+    }
+
+    #[inline]
+    pub fn full_height(&self) -> u32 {
+        self.full_height
+    }
+
+    #[inline]
+    fn index(&self, i: u32, j: u32) -> usize {
+        ((j - self.window.y) * self.window.width + (i - self.window.x)) as usize
+    }
+
+    /// Full-image coordinates of the pixel at `idx` in the pixel buffer.
+    #[inline]
+    pub(crate) fn coordinates(&self, idx: usize) -> (u32, u32) {
+        let idx = idx as u32;
+        (
+            self.window.x + idx % self.window.width,
+            self.window.y + idx / self.window.width,
+        )
+    }
+
+    /// Mean radiance of pixel `(i, j)`, including its share of the outliers around it.
+    pub fn color(&self, i: u32, j: u32) -> Color {
+        let mut color = self.pixels[self.index(i, j)].color();
+        for (ni, nj) in self.neighborhood(i, j) {
+            let outlier_color = self.pixels[self.index(ni, nj)].outlier_color();
+            if outlier_color != Color::zero() {
+                color += outlier_color / self.neighborhood(ni, nj).count() as f64;
+            }
+        }
+
+        color
+    }
+
+    /// Pixels of the film in the 3x3 neighborhood of `(i, j)`, including itself.
+    fn neighborhood(&self, i: u32, j: u32) -> impl Iterator<Item = (u32, u32)> + '_ {
+        let columns = i.saturating_sub(1)..=i + 1;
+        let rows = j.saturating_sub(1)..=j + 1;
+
+        rows.flat_map(move |nj| columns.clone().map(move |ni| (ni, nj)))
+            .filter(|&(ni, nj)| self.window.contains(ni, nj))
+    }
+
+    /// Number of samples accumulated in pixel `(i, j)`.
+    pub fn samples(&self, i: u32, j: u32) -> u32 {
+        self.pixels[self.index(i, j)].samples
+    }
+
+    /// Adds the samples of the pixels `other` shares with this film, e.g. to stitch crops
+    /// rendered separately into a full image.
+    pub fn merge(&mut self, other: &Film) {
+        for (idx, pixel) in other.pixels.iter().enumerate() {
+            let (i, j) = other.coordinates(idx);
+            if self.window.contains(i, j) {
+                let idx = self.index(i, j);
+                self.pixels[idx].merge(pixel);
+            }
+        }
+    }
+
+    /// Estimated noise level of the whole image: the mean relative standard error of all pixels.
+    /// An empty film has no noise.
+    pub fn noise(&self) -> f64 {
+        if self.pixels.is_empty() {
+            return 0.;
+        }
+
+        let total = self
+            .pixels
+            .iter()
+            .map(|pixel| pixel.relative_error())
+            .sum::<f64>();
+
+        total / (self.pixels.len() as f64)
+    }
+
+    fn display_rgb(&self, color: Color, tone_mapping: &ToneMapping) -> [u8; 3] {
+        let color = self.working_space.convert(color, ColorSpace::Rec709);
+
+        color_to_rgb(tone_mapping.apply(color))
+    }
+
+    /// Saves the pixels of the film's window in the format given by the extension of `path`.
+    pub fn save(
+        &self,
+        path: &str,
+        post_effects: &[PostEffect],
+        tone_mapping: &ToneMapping,
+    ) -> image::ImageResult<()> {
+        let format = OutputFormat::from_path(path);
+        self.save_as(path, format, post_effects, tone_mapping)
+    }
+
+    /// Saves the pixels of the film's window as `format`.
+    pub fn save_as(
+        &self,
+        path: &str,
+        format: OutputFormat,
+        post_effects: &[PostEffect],
+        tone_mapping: &ToneMapping,
+    ) -> image::ImageResult<()> {
+        self.write(path, format, post_effects, tone_mapping, self.window)
+    }
+
+    /// Saves an image of the full size in the format given by the extension of `path`, leaving
+    /// pixels outside the film's window black.
+    pub fn save_full_frame(
+        &self,
+        path: &str,
+        post_effects: &[PostEffect],
+        tone_mapping: &ToneMapping,
+    ) -> image::ImageResult<()> {
+        let format = OutputFormat::from_path(path);
+        self.save_full_frame_as(path, format, post_effects, tone_mapping)
+    }
+
+    /// Saves an image of the full size as `format`, leaving pixels outside the film's window black.
+    pub fn save_full_frame_as(
+        &self,
+        path: &str,
+        format: OutputFormat,
+        post_effects: &[PostEffect],
+        tone_mapping: &ToneMapping,
+    ) -> image::ImageResult<()> {
+        let region = CropWindow::new(0, 0, self.full_width, self.full_height);
+        self.write(path, format, post_effects, tone_mapping, region)
+    }
+
+    /// Tone-mapped 8-bit image of the film's window, as it would be saved to an LDR file.
+    pub fn to_image(&self, post_effects: &[PostEffect], tone_mapping: &ToneMapping) -> RgbImage {
+        let buffer = self.post_processed(self.window, post_effects);
+        RgbImage::from_raw(
+            buffer.width,
+            buffer.height,
+            self.display_pixels(&buffer, tone_mapping),
+        )
+        .unwrap()
+    }
+
+    fn post_processed(&self, region: CropWindow, post_effects: &[PostEffect]) -> FrameBuffer {
+        let mut buffer = FrameBuffer {
+            width: region.width,
+            height: region.height,
+            pixels: self.layer(region, Color::zero(), |i, j| self.color(i, j)),
+            offset: Vector2::new(region.x as f64, region.y as f64),
+            full_size: Vector2::new(self.full_width as f64, self.full_height as f64),
+        };
+        for effect in post_effects {
+            effect.apply(&mut buffer);
+        }
+
+        buffer
+    }
+
+    fn display_pixels(&self, buffer: &FrameBuffer, tone_mapping: &ToneMapping) -> Vec<u8> {
+        buffer
+            .pixels
+            .iter()
+            .flat_map(|&color| self.display_rgb(color, tone_mapping))
+            .collect()
+    }
+
+    fn write(
+        &self,
+        path: &str,
+        format: OutputFormat,
+        post_effects: &[PostEffect],
+        tone_mapping: &ToneMapping,
+        region: CropWindow,
+    ) -> image::ImageResult<()> {
+        let buffer = self.post_processed(region, post_effects);
+
+        // Float formats hold scene-linear radiance, so only exposure and white balance are applied.
+        let linear = |space| -> Vec<_> {
+            buffer
+                .pixels
+                .iter()
+                .map(|&color| tone_mapping.expose(self.working_space.convert(color, space)))
+                .collect()
+        };
+
+        match format {
+            OutputFormat::Ldr => {
+                let buf = self.display_pixels(&buffer, tone_mapping);
+
+                image::save_buffer(
+                    path,
+                    &buf,
+                    region.width,
+                    region.height,
+                    image::ColorType::Rgb8,
+                )
+            }
+            OutputFormat::Hdr => output::write_hdr(path, region, &linear(ColorSpace::Rec709)),
+            OutputFormat::Pfm => output::write_pfm(path, region, &linear(ColorSpace::Rec709)),
+            OutputFormat::Exr { precision, aovs } => {
+                let features = aovs.then(|| {
+                    let missed = Pixel::new().features();
+                    self.layer(region, missed, |i, j| {
+                        self.pixels[self.index(i, j)].features()
+                    })
+                });
+
+                output::write_exr(
+                    path,
+                    region,
+                    precision,
+                    self.working_space,
+                    &linear(self.working_space),
+                    features.as_deref(),
+                )
+            }
+        }
+    }
+
+    /// Values of `f` at the pixels of `region` in row-major order, `outside` where the film has no
+    /// pixel.
+    fn layer<T: Clone>(&self, region: CropWindow, outside: T, f: impl Fn(u32, u32) -> T) -> Vec<T> {
+        (region.y..region.y + region.height)
+            .flat_map(|j| (region.x..region.x + region.width).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                if self.window.contains(i, j) {
+                    f(i, j)
+                } else {
+                    outside.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+impl Encode for CropWindow {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.x)
+            .put(&self.y)
+            .put(&self.width)
+            .put(&self.height);
+    }
+}
+
+impl Decode for CropWindow {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self::new(
+            decoder.get()?,
+            decoder.get()?,
+            decoder.get()?,
+            decoder.get()?,
+        ))
+    }
+}
+
+impl Encode for Features {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.albedo).put(&self.normal).put(&self.depth);
+    }
+}
+
+impl Decode for Features {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            albedo: decoder.get()?,
+            normal: decoder.get()?,
+            depth: decoder.get()?,
+        })
+    }
+}
+
+impl Encode for Pixel {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.sum)
+            .put(&self.sum_sq)
+            .put(&self.samples)
+            .put(&self.outliers)
+            .put(&self.outlier_samples)
+            .put(&self.features)
+            .put(&self.hits);
+    }
+}
+
+impl Decode for Pixel {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            sum: decoder.get()?,
+            sum_sq: decoder.get()?,
+            samples: decoder.get()?,
+            outliers: decoder.get()?,
+            outlier_samples: decoder.get()?,
+
+            features: decoder.get()?,
+            hits: decoder.get()?,
+        })
+    }
+}
+
+impl Encode for Film {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.window)
+            .put(&self.full_width)
+            .put(&self.full_height)
+            .put(&self.working_space)
+            .put(&self.outlier_rejection)
+            .put(&self.pixels);
+    }
+}
+
+impl Decode for Film {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let film = Self {
+            window: decoder.get()?,
+            full_width: decoder.get()?,
+            full_height: decoder.get()?,
+            working_space: decoder.get()?,
+            outlier_rejection: decoder.get()?,
+            pixels: decoder.get()?,
+        };
+        if film.pixels.len() as u64 != film.window.width as u64 * film.window.height as u64 {
+            return Err(invalid_data("film size does not match its window"));
+        }
+
+        Ok(film)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoded_film_decodes_to_the_same_pixels() {
+        let mut film = Film::new(CropWindow::new(2, 1, 3, 2), 8, 4, ColorSpace::Rec709);
+        film.reject_outliers(3.);
+        for (idx, pixel) in film.pixels.iter_mut().enumerate() {
+            for sample in 0..=idx {
+                pixel.add_sample(Color::new(sample as f64, 0.5, 1.), None);
+            }
+        }
+
+        let mut encoder = Encoder::new();
+        encoder.put(&film);
+        let message = encoder.finish();
+        let decoded: Film = Decoder::new(&message).get().unwrap();
+
+        assert!(decoded.window() == film.window());
+        assert_eq!(decoded.full_width(), 8);
+        assert_eq!(decoded.outlier_rejection(), Some(3.));
+        for j in 1..3 {
+            for i in 2..5 {
+                assert_eq!(decoded.samples(i, j), film.samples(i, j));
+                assert_eq!(decoded.color(i, j), film.color(i, j));
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_film_larger_than_its_window() {
+        let film = Film::new(CropWindow::new(0, 0, 2, 2), 2, 2, ColorSpace::Rec709);
+        let mut encoder = Encoder::new();
+        encoder
+            .put(&CropWindow::new(0, 0, 1, 1))
+            .put(&2u32)
+            .put(&2u32)
+            .put(&film.working_space)
+            .put(&film.outlier_rejection)
+            .put(&film.pixels);
+        let message = encoder.finish();
+
+        assert!(Decoder::new(&message).get::<Film>().is_err());
+    }
+}
@@ -0,0 +1,135 @@
+use super::{CropWindow, Features};
+use crate::{colorspace::ColorSpace, Color};
+use exr::prelude::*;
+use image::{codecs::hdr::HdrEncoder, ImageResult, Rgb};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+/// File format of a saved film.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Tone-mapped 8-bit sRGB image in any format supported by `image`, e.g. PNG.
+    Ldr,
+    /// Scene-linear OpenEXR in the working space of the film. With `aovs`, the albedo, normal and
+    /// depth of the first hits are stored as additional channels `albedo.RGB`, `N.XYZ` and `Z`.
+    Exr { precision: ExrPrecision, aovs: bool },
+    /// Scene-linear Radiance RGBE in Rec. 709.
+    Hdr,
+    /// Scene-linear portable float map in Rec. 709.
+    Pfm,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+impl OutputFormat {
+    /// Format matching the extension of `path`: half-float EXR for `.exr`, and 8-bit for
+    /// extensions other than `.exr`, `.hdr` and `.pfm`.
+    pub fn from_path(path: &str) -> Self {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+
+        match extension.as_deref() {
+            Some("exr") => Self::Exr {
+                precision: ExrPrecision::Half,
+                aovs: false,
+            },
+            Some("hdr") => Self::Hdr,
+            Some("pfm") => Self::Pfm,
+            _ => Self::Ldr,
+        }
+    }
+}
+
+pub(super) fn write_hdr(path: &str, region: CropWindow, colors: &[Color]) -> ImageResult<()> {
+    let pixels: Vec<_> = colors
+        .iter()
+        .map(|color| Rgb([color.x as f32, color.y as f32, color.z as f32]))
+        .collect();
+
+    let writer = BufWriter::new(File::create(path)?);
+    HdrEncoder::new(writer).encode(&pixels, region.width as usize, region.height as usize)
+}
+
+pub(super) fn write_pfm(path: &str, region: CropWindow, colors: &[Color]) -> ImageResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    // A negative scale marks little-endian data.
+    write!(writer, "PF\n{} {}\n-1.0\n", region.width, region.height)?;
+
+    // Rows are stored bottom to top.
+    for row in colors.chunks(region.width as usize).rev() {
+        for color in row {
+            for c in [color.x, color.y, color.z] {
+                writer.write_all(&(c as f32).to_le_bytes())?;
+            }
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+pub(super) fn write_exr(
+    path: &str,
+    region: CropWindow,
+    precision: ExrPrecision,
+    working_space: ColorSpace,
+    colors: &[Color],
+    features: Option<&[Features]>,
+) -> ImageResult<()> {
+    let samples = |values: Vec<f64>| match precision {
+        ExrPrecision::Half => FlatSamples::F16(values.into_iter().map(f16::from_f64).collect()),
+        ExrPrecision::Float => FlatSamples::F32(values.into_iter().map(|v| v as f32).collect()),
+    };
+    let channel = |name: &str, values: Vec<f64>| AnyChannel::new(name, samples(values));
+
+    let mut channels = SmallVec::new();
+    for (k, name) in ["R", "G", "B"].into_iter().enumerate() {
+        channels.push(channel(name, colors.iter().map(|c| c[k]).collect()));
+    }
+
+    if let Some(features) = features {
+        for (k, name) in ["albedo.R", "albedo.G", "albedo.B"].into_iter().enumerate() {
+            channels.push(channel(
+                name,
+                features.iter().map(|f| f.albedo[k]).collect(),
+            ));
+        }
+        for (k, name) in ["N.X", "N.Y", "N.Z"].into_iter().enumerate() {
+            channels.push(channel(
+                name,
+                features.iter().map(|f| f.normal[k]).collect(),
+            ));
+        }
+
+        // Depth needs the full float range regardless of the precision of the colors.
+        let depth = features.iter().map(|f| f.depth as f32).collect();
+        channels.push(AnyChannel::new("Z", FlatSamples::F32(depth)));
+    }
+
+    let size = (region.width as usize, region.height as usize);
+    let mut image = Image::from_channels(size, AnyChannels::sort(channels));
+
+    let [red, green, blue, white] = working_space
+        .chromaticities()
+        .map(|[x, y]| Vec2(x as f32, y as f32));
+    image.attributes.chromaticities = Some(attribute::Chromaticities {
+        red,
+        green,
+        blue,
+        white,
+    });
+
+    image
+        .write()
+        .to_file(path)
+        .map_err(|error| io::Error::other(error).into())
+}
@@ -1,8 +1,17 @@
-use super::{Hittable, HittableList};
-use crate::ray::Ray;
+use super::{shared_build_time, tag, Hittable, HittableList, Seen};
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, EncodeObject, Encoder},
+    ray::Ray,
+    stats::{self, Counter},
+};
 use cgmath::{prelude::*, Point3, Vector3};
 use rand::prelude::*;
-use std::{ops::Range, sync::Arc};
+use std::{
+    io,
+    ops::Range,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 #[derive(Clone, Copy)]
 pub(crate) struct Aabb {
@@ -96,14 +105,30 @@ impl Aabb {
     }
 }
 
-struct BvhNode {
+impl Encode for Aabb {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.center).put(&self.half_extents);
+    }
+}
+
+impl Decode for Aabb {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            center: decoder.get()?,
+            half_extents: decoder.get()?,
+        })
+    }
+}
+
+pub(super) struct BvhNode {
     left: Arc<dyn Hittable + Send + Sync>,
     right: Arc<dyn Hittable + Send + Sync>,
     aabb: Aabb,
 }
 
 impl Hittable for BvhNode {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<super::HitPayload> {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<super::HitPayload<'_>> {
+        stats::count(Counter::BvhNodes);
         if !self.aabb.hit(ray, range.clone()) {
             return None;
         }
@@ -125,6 +150,31 @@ impl Hittable for BvhNode {
     fn bounding_box(&self) -> Aabb {
         self.aabb
     }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        shared_build_time(&self.left, seen) + shared_build_time(&self.right, seen)
+    }
+}
+
+impl EncodeObject for BvhNode {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        // Leaves hold their single object on both sides, which the encoder sends only once.
+        encoder
+            .put(&tag::BVH_NODE)
+            .put(&self.aabb)
+            .put(&self.left)
+            .put(&self.right);
+    }
+}
+
+impl Decode for BvhNode {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            aabb: decoder.get()?,
+            left: decoder.get()?,
+            right: decoder.get()?,
+        })
+    }
 }
 
 impl BvhNode {
@@ -139,6 +189,10 @@ impl BvhNode {
         };
 
         let (left, right) = match objects.len() {
+            0 => {
+                let empty: Arc<dyn Hittable + Send + Sync> = Arc::new(HittableList::new());
+                (empty.clone(), empty)
+            }
             1 => (objects[0].clone(), objects[0].clone()),
             2 => {
                 if aabb_cmp(objects[0].bounding_box(), objects[1].bounding_box()).is_le() {
@@ -166,22 +220,38 @@ impl BvhNode {
 
 pub struct Bvh {
     root: Arc<BvhNode>,
+    /// Time it took to build the tree, leaving out the BVHs of the objects in it.
+    build_time: Duration,
 }
 
 impl Hittable for Bvh {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<super::HitPayload> {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<super::HitPayload<'_>> {
         self.root.hit(ray, range)
     }
 
     fn bounding_box(&self) -> Aabb {
         self.root.aabb
     }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        self.build_time + self.root.build_time(seen)
+    }
+}
+
+impl EncodeObject for Bvh {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        self.root.encode_object(encoder);
+    }
 }
 
 impl Bvh {
     pub fn from_list(world: &mut HittableList) -> Self {
+        let start = Instant::now();
+        let root = BvhNode::new(&mut world.objects);
+
         Self {
-            root: BvhNode::new(&mut world.objects),
+            root,
+            build_time: start.elapsed(),
         }
     }
 }
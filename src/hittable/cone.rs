@@ -0,0 +1,141 @@
+use super::{azimuth, bvh::Aabb, disk::disk_bounds, tag, Frame, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use std::{io, sync::Arc};
+
+/// Cone from the center of its `base` to its `apex`, closed by a flat base.
+///
+/// On the side `u` goes around the axis and `v` up from the base. On the base `u` goes around the
+/// center and `v` out from it to the rim, as on a [`Disk`](super::Disk).
+pub struct Cone {
+    base: Point3<f64>,
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    aabb: Aabb,
+
+    material: Material,
+}
+
+impl Cone {
+    pub fn new(base: Point3<f64>, apex: Point3<f64>, radius: f64, material: Material) -> Arc<Self> {
+        let frame = Frame::new(apex - base);
+        let aabb = disk_bounds(base, frame.axis, radius);
+        let aabb = Aabb::covering(&aabb, &Aabb::from_min_max(apex, apex)).padding();
+
+        Arc::new(Self {
+            base,
+            frame,
+            height: (apex - base).magnitude(),
+            radius,
+            aabb,
+
+            material,
+        })
+    }
+
+    /// Slope of the radius, which shrinks to zero at the apex.
+    fn slope(&self) -> f64 {
+        self.radius / self.height
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, mut range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::ShapeTests);
+        let local = self.frame.local_ray(ray, self.base);
+        let (o, d) = (local.origin, local.direction);
+        let k2 = self.slope() * self.slope();
+
+        // Local normal and texture coordinates of the closest hit so far.
+        let mut closest = None;
+
+        // x^2 + z^2 = k^2 (h - y)^2, which also holds for the mirrored cone above the apex.
+        let w = self.height - o.y;
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z + k2 * w * d.y;
+        let c = o.x * o.x + o.z * o.z - k2 * w * w;
+        let roots = if a.abs() < 1e-12 {
+            // Parallel to the surface, so the ray crosses it once at most.
+            [-0.5 * c / half_b, f64::NAN]
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            let sqrtd = discriminant.sqrt();
+            let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+            [t0.min(t1), t0.max(t1)]
+        };
+        for t in roots {
+            let point = local.at(t);
+            if range.contains(&t) && (0. ..=self.height).contains(&point.y) {
+                let normal = Vector3::new(point.x, k2 * (self.height - point.y), point.z);
+                let normal = if normal.magnitude2() > 0. {
+                    normal.normalize()
+                } else {
+                    Vector3::unit_y()
+                };
+                closest = Some((normal, azimuth(point), point.y / self.height));
+                range.end = t;
+                break;
+            }
+        }
+
+        let t = -o.y / d.y;
+        if range.contains(&t) {
+            let point = local.at(t);
+            let distance2 = point.x * point.x + point.z * point.z;
+            if distance2 <= self.radius * self.radius {
+                let v = distance2.sqrt() / self.radius;
+                closest = Some((-Vector3::unit_y(), azimuth(point), v));
+                range.end = t;
+            }
+        }
+
+        let (normal, u, v) = closest?;
+        let t = range.end;
+        Some(HitPayload::new(
+            ray,
+            ray.at(t),
+            self.frame.to_world(normal),
+            t,
+            u,
+            v,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl EncodeObject for Cone {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::CONE)
+            .put(&self.base)
+            .put(&self.frame)
+            .put(&self.height)
+            .put(&self.radius)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Cone {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            base: decoder.get()?,
+            frame: decoder.get()?,
+            height: decoder.get()?,
+            radius: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
@@ -1,8 +1,14 @@
-use super::{bvh::Aabb, HitPayload, Hittable, Range};
-use crate::{material::Material, ray::Ray, texture::Texture};
+use super::{bvh::Aabb, shared_build_time, tag, HitPayload, Hittable, Range, Seen};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+    texture::Texture,
+};
 use cgmath::{prelude::*, Vector3};
 use rand::prelude::*;
-use std::sync::Arc;
+use std::{io, sync::Arc, time::Duration};
 
 pub struct ConstantMedium {
     neg_inv_density: f64,
@@ -11,7 +17,8 @@ pub struct ConstantMedium {
 }
 
 impl Hittable for ConstantMedium {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload> {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::MediumTests);
         let mut rng = rand::thread_rng();
 
         let mut payload1 = match self.boundary.hit(ray, f64::NEG_INFINITY..f64::INFINITY) {
@@ -53,10 +60,12 @@ impl Hittable for ConstantMedium {
         Some(HitPayload {
             point,
             normal: Vector3::unit_z(),
+            shading_normal: Vector3::unit_z(),
             t,
             front_face: false,
             u: 0.0,
             v: 0.0,
+            vertex_color: None,
             material: &self.phase_function,
         })
     }
@@ -64,6 +73,20 @@ impl Hittable for ConstantMedium {
     fn bounding_box(&self) -> Aabb {
         self.boundary.bounding_box()
     }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        shared_build_time(&self.boundary, seen)
+    }
+}
+
+impl EncodeObject for ConstantMedium {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::CONSTANT_MEDIUM)
+            .put(&self.neg_inv_density)
+            .put(&self.boundary)
+            .put(&self.phase_function);
+    }
 }
 
 impl ConstantMedium {
@@ -75,8 +98,18 @@ impl ConstantMedium {
     ) -> Arc<Self> {
         Arc::new(Self {
             neg_inv_density: -density.recip(),
-            boundary: boundary,
+            boundary,
             phase_function: Material::isotropic(phase),
         })
     }
 }
+
+impl Decode for ConstantMedium {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            neg_inv_density: decoder.get()?,
+            boundary: decoder.get()?,
+            phase_function: decoder.get()?,
+        })
+    }
+}
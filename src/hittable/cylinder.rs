@@ -0,0 +1,129 @@
+use super::{azimuth, bvh::Aabb, disk::disk_bounds, tag, Frame, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use std::{io, sync::Arc};
+
+/// Cylinder from the center of its `base` to that of its `top`, closed by flat caps.
+///
+/// On the side `u` goes around the axis and `v` up from the base. On the caps `u` goes around the
+/// center and `v` out from it to the rim, as on a [`Disk`](super::Disk).
+pub struct Cylinder {
+    base: Point3<f64>,
+    frame: Frame,
+    height: f64,
+    radius: f64,
+    aabb: Aabb,
+
+    material: Material,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3<f64>, top: Point3<f64>, radius: f64, material: Material) -> Arc<Self> {
+        let frame = Frame::new(top - base);
+        let aabb = Aabb::covering(
+            &disk_bounds(base, frame.axis, radius),
+            &disk_bounds(top, frame.axis, radius),
+        )
+        .padding();
+
+        Arc::new(Self {
+            base,
+            frame,
+            height: (top - base).magnitude(),
+            radius,
+            aabb,
+
+            material,
+        })
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, mut range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::ShapeTests);
+        let local = self.frame.local_ray(ray, self.base);
+        let (o, d) = (local.origin, local.direction);
+        let radius2 = self.radius * self.radius;
+
+        // Local normal and texture coordinates of the closest hit so far.
+        let mut closest = None;
+
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - radius2;
+        let discriminant = half_b * half_b - a * c;
+        if a > 0. && discriminant >= 0. {
+            let sqrtd = discriminant.sqrt();
+            for t in [(-half_b - sqrtd) / a, (-half_b + sqrtd) / a] {
+                let point = local.at(t);
+                if range.contains(&t) && (0. ..=self.height).contains(&point.y) {
+                    let normal = Vector3::new(point.x, 0., point.z) / self.radius;
+                    closest = Some((normal, azimuth(point), point.y / self.height));
+                    range.end = t;
+                    break;
+                }
+            }
+        }
+
+        for (y, normal) in [(0., -Vector3::unit_y()), (self.height, Vector3::unit_y())] {
+            let t = (y - o.y) / d.y;
+            if !range.contains(&t) {
+                continue;
+            }
+            let point = local.at(t);
+            let distance2 = point.x * point.x + point.z * point.z;
+            if distance2 <= radius2 {
+                closest = Some((normal, azimuth(point), distance2.sqrt() / self.radius));
+                range.end = t;
+            }
+        }
+
+        let (normal, u, v) = closest?;
+        let t = range.end;
+        Some(HitPayload::new(
+            ray,
+            ray.at(t),
+            self.frame.to_world(normal),
+            t,
+            u,
+            v,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl EncodeObject for Cylinder {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::CYLINDER)
+            .put(&self.base)
+            .put(&self.frame)
+            .put(&self.height)
+            .put(&self.radius)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Cylinder {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            base: decoder.get()?,
+            frame: decoder.get()?,
+            height: decoder.get()?,
+            radius: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
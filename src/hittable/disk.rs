@@ -0,0 +1,103 @@
+use super::{azimuth, bvh::Aabb, tag, Frame, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
+use cgmath::{Point3, Vector3};
+use std::{io, sync::Arc};
+
+/// Flat disk facing along its normal. `u` goes around the center and `v` out from it to the rim.
+pub struct Disk {
+    center: Point3<f64>,
+    frame: Frame,
+    radius: f64,
+    aabb: Aabb,
+
+    material: Material,
+}
+
+impl Disk {
+    pub fn new(
+        center: Point3<f64>,
+        normal: Vector3<f64>,
+        radius: f64,
+        material: Material,
+    ) -> Arc<Self> {
+        let frame = Frame::new(normal);
+        let aabb = disk_bounds(center, frame.axis, radius).padding();
+
+        Arc::new(Self {
+            center,
+            frame,
+            radius,
+            aabb,
+
+            material,
+        })
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::ShapeTests);
+        let local = self.frame.local_ray(ray, self.center);
+
+        let t = -local.origin.y / local.direction.y;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let point = local.at(t);
+        let distance2 = point.x * point.x + point.z * point.z;
+        if distance2 > self.radius * self.radius {
+            return None;
+        }
+
+        Some(HitPayload::new(
+            ray,
+            ray.at(t),
+            self.frame.axis,
+            t,
+            azimuth(point),
+            distance2.sqrt() / self.radius,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl EncodeObject for Disk {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::DISK)
+            .put(&self.center)
+            .put(&self.frame)
+            .put(&self.radius)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Disk {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            center: decoder.get()?,
+            frame: decoder.get()?,
+            radius: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
+
+/// Bounds of a circle of `radius` around `center`, in the plane normal to the unit `axis`.
+pub(super) fn disk_bounds(center: Point3<f64>, axis: Vector3<f64>, radius: f64) -> Aabb {
+    let extent = axis.map(|a| radius * (1. - a * a).max(0.).sqrt());
+    Aabb::from_min_max(center - extent, center + extent)
+}
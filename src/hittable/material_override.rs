@@ -0,0 +1,67 @@
+use super::{bvh::Aabb, shared_build_time, tag, HitPayload, Hittable, Range, Seen};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+};
+use std::{io, sync::Arc, time::Duration};
+
+/// Object whose hits are shaded with another material than its own, e.g. to recolor a loaded
+/// mesh without rebuilding it.
+pub struct MaterialOverride {
+    object: Arc<dyn Hittable + Send + Sync>,
+    material: Material,
+}
+
+impl Hittable for MaterialOverride {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        let mut payload = self.object.hit(ray, range)?;
+        payload.material = &self.material;
+
+        Some(payload)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        shared_build_time(&self.object, seen)
+    }
+}
+
+impl EncodeObject for MaterialOverride {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::MATERIAL_OVERRIDE)
+            .put(&self.object)
+            .put(&self.material);
+    }
+}
+
+impl MaterialOverride {
+    #[allow(private_bounds)]
+    pub fn new<H: Hittable + Send + Sync + 'static>(
+        object: Arc<H>,
+        material: Material,
+    ) -> Arc<Self> {
+        Self::new_shared(object, material)
+    }
+
+    /// [`MaterialOverride::new`] for objects whose type is not known statically.
+    pub(crate) fn new_shared(
+        object: Arc<dyn Hittable + Send + Sync>,
+        material: Material,
+    ) -> Arc<Self> {
+        Arc::new(Self { object, material })
+    }
+}
+
+impl Decode for MaterialOverride {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            object: decoder.get()?,
+            material: decoder.get()?,
+        })
+    }
+}
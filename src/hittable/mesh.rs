@@ -0,0 +1,399 @@
+use super::{bvh::Aabb, tag, HitPayload, Hittable, Range, Seen};
+use crate::{
+    distributed::wire::{invalid_data, Decode, Decoder, Encode, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+    Color,
+};
+use cgmath::{prelude::*, Point3, Vector2, Vector3};
+use std::{
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Vertex attributes indexed by the faces of one or more meshes.
+#[derive(Default)]
+pub struct VertexBuffers {
+    pub positions: Vec<Point3<f64>>,
+    pub normals: Vec<Vector3<f64>>,
+    pub uvs: Vec<Vector2<f64>>,
+    /// Linear sRGB colors.
+    pub colors: Vec<Color>,
+}
+
+/// Triangle of a [`TriangleMesh`], given by the indices of its three corners into each buffer.
+/// Faces without normals are shaded flat, faces without UVs use their barycentric coordinates.
+/// Colors are interpolated for a [`VertexColorTexture`].
+///
+/// [`VertexColorTexture`]: crate::texture::VertexColorTexture
+#[derive(Clone, Copy)]
+pub struct MeshFace {
+    pub positions: [u32; 3],
+    pub normals: Option<[u32; 3]>,
+    pub uvs: Option<[u32; 3]>,
+    pub colors: Option<[u32; 3]>,
+    /// Index into the materials of the mesh.
+    pub material: u32,
+}
+
+/// Node of the BVH of a mesh, stored depth-first.
+struct Node {
+    aabb: Aabb,
+    /// First face of a leaf, or the index of the second child of an interior node, whose first
+    /// child directly follows it.
+    offset: u32,
+    /// Number of faces of a leaf, 0 for interior nodes.
+    count: u32,
+}
+
+/// Indexed triangle mesh with its own BVH over the faces.
+pub struct TriangleMesh {
+    buffers: Arc<VertexBuffers>,
+    faces: Vec<MeshFace>,
+    materials: Vec<Material>,
+    nodes: Vec<Node>,
+    build_time: Duration,
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        // Median splits keep the tree balanced, so its depth stays far below the stack size.
+        let mut stack = [0u32; 64];
+        let mut len = 1;
+
+        let mut closest: Option<HitPayload> = None;
+        let mut end = range.end;
+        while len > 0 {
+            len -= 1;
+            let index = stack[len] as usize;
+            let node = &self.nodes[index];
+
+            stats::count(Counter::BvhNodes);
+            if !node.aabb.hit(ray, range.start..end) {
+                continue;
+            }
+
+            if node.count == 0 {
+                stack[len] = node.offset;
+                stack[len + 1] = index as u32 + 1;
+                len += 2;
+                continue;
+            }
+
+            let faces = &self.faces[node.offset as usize..][..node.count as usize];
+            for face in faces {
+                if let Some(payload) = self.hit_face(face, ray, range.start..end) {
+                    end = payload.t;
+                    closest = Some(payload);
+                }
+            }
+        }
+
+        closest
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes.first().map_or(Aabb::empty(), |root| root.aabb)
+    }
+
+    fn build_time(&self, _seen: &mut Seen) -> Duration {
+        self.build_time
+    }
+}
+
+impl EncodeObject for TriangleMesh {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::MESH)
+            .put(&self.buffers)
+            .put(&self.faces)
+            .put(&self.materials);
+    }
+}
+
+impl TriangleMesh {
+    /// Mesh of `faces` indexing into `buffers`, which may be shared with other meshes.
+    ///
+    /// # Panics
+    ///
+    /// If a face indexes past the end of a buffer or of `materials`.
+    pub fn new(
+        buffers: Arc<VertexBuffers>,
+        faces: Vec<MeshFace>,
+        materials: Vec<Material>,
+    ) -> Arc<Self> {
+        if let Err(message) = validate(&buffers, &faces, materials.len()) {
+            panic!("{}", message);
+        }
+
+        Arc::new(Self::build(buffers, faces, materials))
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    fn build(buffers: Arc<VertexBuffers>, faces: Vec<MeshFace>, materials: Vec<Material>) -> Self {
+        let start = Instant::now();
+        let mut primitives: Vec<_> = faces
+            .into_iter()
+            .map(|face| Primitive::new(face, &buffers.positions))
+            .collect();
+        let mut nodes = Vec::with_capacity(primitives.len() / 2);
+        if !primitives.is_empty() {
+            build_node(&mut nodes, &mut primitives, 0);
+        }
+        let faces = primitives
+            .into_iter()
+            .map(|primitive| primitive.face)
+            .collect();
+
+        Self {
+            buffers,
+            faces,
+            materials,
+            nodes,
+            build_time: start.elapsed(),
+        }
+    }
+
+    fn hit_face(&self, face: &MeshFace, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::TriangleTests);
+
+        // Möller-Trumbore: solve for the distance and two barycentric coordinates at once.
+        let [a, b, c] = face.positions.map(|i| self.buffers.positions[i as usize]);
+        let e1 = b - a;
+        let e2 = c - a;
+
+        let pvec = ray.direction.cross(e2);
+        let det = e1.dot(pvec);
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let inv_det = det.recip();
+
+        let tvec = ray.origin - a;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(e1);
+        let b2 = ray.direction.dot(qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+
+        let t = e2.dot(qvec) * inv_det;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        let weights = [1. - b1 - b2, b1, b2];
+        let shading_normal = face
+            .normals
+            .map(|normals| interpolate(normals.map(|i| self.buffers.normals[i as usize]), weights))
+            .filter(|normal| normal.magnitude2() > 0.);
+        // Vertex normals also tell the outside, they are more reliable than the winding order.
+        let mut outward_normal = e1.cross(e2).normalize();
+        if shading_normal.is_some_and(|normal| normal.dot(outward_normal) < 0.) {
+            outward_normal = -outward_normal;
+        }
+
+        let (u, v) = match face.uvs {
+            Some(uvs) => {
+                let uv = interpolate(uvs.map(|i| self.buffers.uvs[i as usize]), weights);
+                (uv.x, uv.y)
+            }
+            None => (b1, b2),
+        };
+
+        let mut payload = HitPayload::new(
+            ray,
+            ray.at(t),
+            outward_normal,
+            t,
+            u,
+            v,
+            &self.materials[face.material as usize],
+        );
+        if let Some(shading_normal) = shading_normal {
+            payload.shade_with(ray, shading_normal);
+        }
+        payload.vertex_color = face
+            .colors
+            .map(|colors| interpolate(colors.map(|i| self.buffers.colors[i as usize]), weights));
+
+        Some(payload)
+    }
+}
+
+fn interpolate<V: VectorSpace<Scalar = f64>>(values: [V; 3], weights: [f64; 3]) -> V {
+    values[0] * weights[0] + values[1] * weights[1] + values[2] * weights[2]
+}
+
+fn validate(buffers: &VertexBuffers, faces: &[MeshFace], materials: usize) -> Result<(), String> {
+    let in_bounds = |indices: [u32; 3], len: usize| indices.iter().all(|&i| (i as usize) < len);
+
+    for (i, face) in faces.iter().enumerate() {
+        let valid = in_bounds(face.positions, buffers.positions.len())
+            && face
+                .normals
+                .is_none_or(|normals| in_bounds(normals, buffers.normals.len()))
+            && face.uvs.is_none_or(|uvs| in_bounds(uvs, buffers.uvs.len()))
+            && face
+                .colors
+                .is_none_or(|colors| in_bounds(colors, buffers.colors.len()))
+            && (face.material as usize) < materials;
+        if !valid {
+            return Err(format!("mesh face {} indexes past the end of a buffer", i));
+        }
+    }
+
+    Ok(())
+}
+
+const LEAF_SIZE: usize = 4;
+
+/// Face with its bounds and centroid, computed once rather than on every level of the build.
+struct Primitive {
+    face: MeshFace,
+    aabb: Aabb,
+    centroid: Point3<f64>,
+}
+
+impl Primitive {
+    fn new(face: MeshFace, positions: &[Point3<f64>]) -> Self {
+        let [a, b, c] = face.positions.map(|i| positions[i as usize]);
+        let minimum = Point3::new(
+            a.x.min(b.x).min(c.x),
+            a.y.min(b.y).min(c.y),
+            a.z.min(b.z).min(c.z),
+        );
+        let maximum = Point3::new(
+            a.x.max(b.x).max(c.x),
+            a.y.max(b.y).max(c.y),
+            a.z.max(b.z).max(c.z),
+        );
+
+        Self {
+            face,
+            aabb: Aabb::from_min_max(minimum, maximum),
+            centroid: Point3::centroid(&[a, b, c]),
+        }
+    }
+}
+
+/// Appends the subtree over `faces`, which start at index `first` of the mesh, splitting them at
+/// the median centroid along the axis of largest spread.
+fn build_node(nodes: &mut Vec<Node>, faces: &mut [Primitive], first: u32) {
+    let aabb = faces
+        .iter()
+        .fold(Aabb::empty(), |aabb, face| {
+            Aabb::covering(&aabb, &face.aabb)
+        })
+        .padding();
+
+    let index = nodes.len();
+    nodes.push(Node {
+        aabb,
+        offset: first,
+        count: faces.len() as u32,
+    });
+    if faces.len() <= LEAF_SIZE {
+        return;
+    }
+
+    let centroids = faces.iter().fold(Aabb::empty(), |aabb, face| {
+        Aabb::covering(&aabb, &Aabb::from_min_max(face.centroid, face.centroid))
+    });
+    let spread = centroids.half_extents;
+    let axis = if spread.x >= spread.y && spread.x >= spread.z {
+        0
+    } else if spread.y >= spread.z {
+        1
+    } else {
+        2
+    };
+    // Faces with coinciding centroids cannot be told apart by a split.
+    if spread[axis].is_nan() || spread[axis] <= 0. {
+        return;
+    }
+
+    let mid = faces.len() / 2;
+    faces.select_nth_unstable_by(mid, |a, b| a.centroid[axis].total_cmp(&b.centroid[axis]));
+    let (left, right) = faces.split_at_mut(mid);
+
+    build_node(nodes, left, first);
+    let second = nodes.len() as u32;
+    build_node(nodes, right, first + mid as u32);
+
+    nodes[index].offset = second;
+    nodes[index].count = 0;
+}
+
+impl Encode for VertexBuffers {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.positions)
+            .put(&self.normals)
+            .put(&self.uvs)
+            .put(&self.colors);
+    }
+}
+
+impl Decode for VertexBuffers {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            positions: decoder.get()?,
+            normals: decoder.get()?,
+            uvs: decoder.get()?,
+            colors: decoder.get()?,
+        })
+    }
+}
+
+impl Encode for MeshFace {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.positions)
+            .put(&self.normals)
+            .put(&self.uvs)
+            .put(&self.colors)
+            .put(&self.material);
+    }
+}
+
+impl Decode for MeshFace {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            positions: decoder.get()?,
+            normals: decoder.get()?,
+            uvs: decoder.get()?,
+            colors: decoder.get()?,
+            material: decoder.get()?,
+        })
+    }
+}
+
+// The BVH is rebuilt rather than sent, so a message cannot describe an inconsistent tree.
+impl Decode for TriangleMesh {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let buffers: Arc<VertexBuffers> = decoder.get()?;
+        let faces: Vec<MeshFace> = decoder.get()?;
+        let materials: Vec<Material> = decoder.get()?;
+        validate(&buffers, &faces, materials.len()).map_err(|message| invalid_data(&message))?;
+
+        Ok(Self::build(buffers, faces, materials))
+    }
+}
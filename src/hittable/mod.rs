@@ -1,36 +1,202 @@
-use crate::{material::Material, ray::Ray};
+use crate::{
+    distributed::wire::{unknown_tag, Decode, Decoder, Encode, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    reflect, Color,
+};
 use cgmath::{prelude::*, Point3, Vector3};
 use std::ops::Range;
-use std::sync::Arc;
+use std::{collections::HashSet, io, sync::Arc, time::Duration};
 
 mod bvh;
+mod cone;
 mod constant_medium;
+mod cylinder;
+mod disk;
+mod material_override;
+mod mesh;
 mod quad;
 mod shpere;
+mod torus;
 mod transform;
+mod triangle;
 
 pub use bvh::Bvh;
+pub use cone::Cone;
 pub use constant_medium::ConstantMedium;
+pub use cylinder::Cylinder;
+pub use disk::Disk;
+pub use material_override::MaterialOverride;
+pub use mesh::{MeshFace, TriangleMesh, VertexBuffers};
 pub use quad::Quad;
 pub use shpere::Sphere;
+pub use torus::Torus;
 pub use transform::Transform;
+pub(crate) use transform::{is_projective, is_singular, linear_part};
+pub use triangle::Triangle;
 
-use bvh::Aabb;
+use bvh::{Aabb, BvhNode};
 
-pub(crate) trait Hittable {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload>;
+pub(crate) trait Hittable: EncodeObject {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>>;
 
     fn bounding_box(&self) -> Aabb;
+
+    /// Time spent building the BVHs within the object, leaving out the objects already `seen`.
+    fn build_time(&self, _seen: &mut Seen) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// Addresses of the shared objects whose build time is already counted.
+pub(crate) type Seen = HashSet<*const ()>;
+
+/// [`Hittable::build_time`] of a shared object, counting an object that is shared several times
+/// once.
+fn shared_build_time(object: &Arc<dyn Hittable + Send + Sync>, seen: &mut Seen) -> Duration {
+    if seen.insert(Arc::as_ptr(object) as *const ()) {
+        object.build_time(seen)
+    } else {
+        Duration::ZERO
+    }
+}
+
+mod tag {
+    pub(super) const LIST: u8 = 0;
+    pub(super) const BVH_NODE: u8 = 1;
+    pub(super) const SPHERE: u8 = 2;
+    pub(super) const QUAD: u8 = 3;
+    pub(super) const TRANSFORM: u8 = 4;
+    pub(super) const CONSTANT_MEDIUM: u8 = 5;
+    pub(super) const MESH: u8 = 6;
+    pub(super) const MATERIAL_OVERRIDE: u8 = 7;
+    pub(super) const DISK: u8 = 8;
+    pub(super) const TRIANGLE: u8 = 9;
+    pub(super) const CYLINDER: u8 = 10;
+    pub(super) const CONE: u8 = 11;
+    pub(super) const TORUS: u8 = 12;
+}
+
+impl Encode for dyn Hittable + Send + Sync {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.encode_object(encoder);
+    }
+}
+
+impl Decode for Arc<dyn Hittable + Send + Sync> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        decoder.shared(decode_object)
+    }
+}
+
+/// Reads an object written by [`EncodeObject::encode_object`] rather than through an `Arc`.
+pub(crate) fn decode_object(decoder: &mut Decoder) -> io::Result<Arc<dyn Hittable + Send + Sync>> {
+    Ok(match decoder.get()? {
+        tag::LIST => Arc::new(decoder.get::<HittableList>()?),
+        tag::BVH_NODE => Arc::new(decoder.get::<BvhNode>()?),
+        tag::SPHERE => Arc::new(decoder.get::<Sphere>()?),
+        tag::QUAD => Arc::new(decoder.get::<Quad>()?),
+        tag::TRANSFORM => Arc::new(decoder.get::<Transform>()?),
+        tag::CONSTANT_MEDIUM => Arc::new(decoder.get::<ConstantMedium>()?),
+        tag::MESH => Arc::new(decoder.get::<TriangleMesh>()?),
+        tag::MATERIAL_OVERRIDE => Arc::new(decoder.get::<MaterialOverride>()?),
+        tag::DISK => Arc::new(decoder.get::<Disk>()?),
+        tag::TRIANGLE => Arc::new(decoder.get::<Triangle>()?),
+        tag::CYLINDER => Arc::new(decoder.get::<Cylinder>()?),
+        tag::CONE => Arc::new(decoder.get::<Cone>()?),
+        tag::TORUS => Arc::new(decoder.get::<Torus>()?),
+        tag => return Err(unknown_tag("hittable", tag)),
+    })
+}
+
+/// Orthonormal frame with `axis` in the place of the y axis, in which shapes around an axis are
+/// solved as if upright at the origin.
+#[derive(Clone, Copy)]
+struct Frame {
+    tangent: Vector3<f64>,
+    axis: Vector3<f64>,
+    bitangent: Vector3<f64>,
+}
+
+impl Frame {
+    fn new(axis: Vector3<f64>) -> Self {
+        let axis = axis.normalize();
+        let helper = if axis.z.abs() > 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_z()
+        };
+        let tangent = axis.cross(helper).normalize();
+
+        Self {
+            tangent,
+            axis,
+            bitangent: tangent.cross(axis),
+        }
+    }
+
+    fn to_local(self, vec: Vector3<f64>) -> Vector3<f64> {
+        Vector3::new(
+            vec.dot(self.tangent),
+            vec.dot(self.axis),
+            vec.dot(self.bitangent),
+        )
+    }
+
+    fn to_world(self, vec: Vector3<f64>) -> Vector3<f64> {
+        vec.x * self.tangent + vec.y * self.axis + vec.z * self.bitangent
+    }
+
+    /// Ray in the frame of a shape placed at `origin`. The distances along it stay the same.
+    fn local_ray(&self, ray: &Ray, origin: Point3<f64>) -> Ray {
+        Ray {
+            origin: Point3::from_vec(self.to_local(ray.origin - origin)),
+            direction: self.to_local(ray.direction),
+            time: ray.time,
+        }
+    }
+}
+
+impl Encode for Frame {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.tangent)
+            .put(&self.axis)
+            .put(&self.bitangent);
+    }
+}
+
+impl Decode for Frame {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            tangent: decoder.get()?,
+            axis: decoder.get()?,
+            bitangent: decoder.get()?,
+        })
+    }
+}
+
+/// Angle around the local y axis as a texture coordinate, starting and ending at -x like the
+/// longitude of a [`Sphere`].
+fn azimuth(local: Point3<f64>) -> f64 {
+    ((-local.z).atan2(local.x) + std::f64::consts::PI) * 0.5 * std::f64::consts::FRAC_1_PI
 }
 
 pub(crate) struct HitPayload<'a> {
     pub(crate) point: Point3<f64>,
+    /// Normal of the actual surface, on the side the ray came from.
     pub(crate) normal: Vector3<f64>,
+    /// Normal to shade with, e.g. interpolated across a mesh face, on the side of `normal`.
+    pub(crate) shading_normal: Vector3<f64>,
     pub(crate) t: f64,
     pub(crate) front_face: bool,
 
     pub(crate) u: f64,
     pub(crate) v: f64,
+    /// Color interpolated from the vertices of a mesh, see [`VertexColorTexture`].
+    ///
+    /// [`VertexColorTexture`]: crate::texture::VertexColorTexture
+    pub(crate) vertex_color: Option<Color>,
 
     pub(crate) material: &'a Material,
 }
@@ -55,24 +221,71 @@ impl<'a> HitPayload<'a> {
         Self {
             point,
             normal,
+            shading_normal: normal,
             t,
             front_face,
 
             u,
             v,
+            vertex_color: None,
 
             material,
         }
     }
+
+    /// Shades with `outward_normal` instead of the geometric normal.
+    ///
+    /// Where the shading normal would mirror the ray below the surface, which shows as black
+    /// facets along the silhouettes of coarse meshes, it is bent towards the geometric normal just
+    /// enough for the mirror direction to graze the surface.
+    pub(crate) fn shade_with(&mut self, ray: &Ray, outward_normal: Vector3<f64>) {
+        let normal = self.normal.normalize();
+        let mut shading_normal = outward_normal.normalize();
+        if shading_normal.dot(normal) < 0. {
+            shading_normal = -shading_normal;
+        }
+
+        let direction = ray.direction.normalize();
+        let mirrored = reflect(direction, shading_normal);
+        if mirrored.dot(normal) < GRAZING {
+            let tangent = mirrored - mirrored.dot(normal) * normal;
+            shading_normal = if tangent.magnitude2() > 0. {
+                // Half vector between the view and a mirror direction just above the surface.
+                let grazing = (tangent.normalize() + GRAZING * normal).normalize();
+                (grazing - direction).normalize()
+            } else {
+                normal
+            };
+        }
+
+        self.shading_normal = shading_normal;
+    }
+
+    /// `direction` if it leaves the surface on the side the ray came from, or on the other side
+    /// when `outside` is false, otherwise its mirror image in the surface. Directions found with
+    /// the shading normal may end up on the wrong side, which would leak light through the surface.
+    pub(crate) fn keep_side(&self, direction: Vector3<f64>, outside: bool) -> Vector3<f64> {
+        let normal = self.normal.normalize();
+        let cos = direction.dot(normal);
+        if (cos > 0.) == outside {
+            direction
+        } else {
+            direction - 2. * cos * normal
+        }
+    }
 }
 
+/// Lowest cosine of a mirror direction with the geometric normal that [`HitPayload::shade_with`]
+/// lets through.
+const GRAZING: f64 = 0.01;
+
 pub struct HittableList {
     pub(crate) objects: Vec<Arc<dyn Hittable + Send + Sync>>,
     pub(crate) aabb: Aabb,
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload> {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
         let hit_payload: Option<HitPayload> = self.objects.iter().fold(None, |closest, object| {
             let end = if let Some(closest) = &closest {
                 closest.t
@@ -92,6 +305,34 @@ impl Hittable for HittableList {
     fn bounding_box(&self) -> Aabb {
         self.aabb
     }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        self.objects
+            .iter()
+            .map(|object| shared_build_time(object, seen))
+            .sum()
+    }
+}
+
+impl EncodeObject for HittableList {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder.put(&tag::LIST).put(&self.objects).put(&self.aabb);
+    }
+}
+
+impl Decode for HittableList {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            objects: decoder.get()?,
+            aabb: decoder.get()?,
+        })
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl HittableList {
@@ -104,10 +345,23 @@ impl HittableList {
 
     #[allow(private_bounds)]
     pub fn push<H: Hittable + Send + Sync + 'static>(&mut self, object: Arc<H>) {
+        self.push_shared(object);
+    }
+
+    pub(crate) fn push_shared(&mut self, object: Arc<dyn Hittable + Send + Sync>) {
         self.aabb = Aabb::covering(&self.aabb, &object.bounding_box());
         self.objects.push(object);
     }
 
+    /// The only object, a BVH over several, or `None` for an empty list.
+    pub(crate) fn into_shared(mut self) -> Option<Arc<dyn Hittable + Send + Sync>> {
+        match self.objects.len() {
+            0 => None,
+            1 => self.objects.pop(),
+            _ => Some(Arc::new(Bvh::from_list(&mut self))),
+        }
+    }
+
     pub fn clear(&mut self) {
         self.objects.clear();
         self.aabb = Aabb::empty();
@@ -116,4 +370,223 @@ impl HittableList {
     pub fn len(&self) -> usize {
         self.objects.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_bvh_build_counts_once() {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let mut spheres = HittableList::new();
+        for x in 0..100 {
+            spheres.push(Sphere::new(
+                Point3::new(x as f64, 0., 0.),
+                0.5,
+                material.clone(),
+            ));
+        }
+        let bvh = Arc::new(Bvh::from_list(&mut spheres));
+
+        let mut world = HittableList::new();
+        world.push(bvh.clone());
+        world.push(bvh.clone());
+        world.push(Transform::new(
+            bvh.clone(),
+            Vector3::unit_y(),
+            cgmath::Quaternion::one(),
+        ));
+
+        let once = bvh.build_time(&mut Seen::new());
+        assert!(once > Duration::ZERO);
+        assert_eq!(world.build_time(&mut Seen::new()), once);
+    }
+
+    #[test]
+    fn encoding_keeps_shared_objects_shared() {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let sphere = Sphere::new(Point3::new(0., 0., -2.), 0.5, material);
+        let mut world = HittableList::new();
+        world.push(sphere.clone());
+        world.push(sphere.clone());
+
+        let mut encoder = Encoder::new();
+        world.encode_object(&mut encoder);
+        let message = encoder.finish();
+        let mut decoder = Decoder::new(&message);
+        assert_eq!(decoder.get::<u8>().unwrap(), tag::LIST);
+        let decoded: HittableList = decoder.get().unwrap();
+
+        assert!(Arc::ptr_eq(&decoded.objects[0], &decoded.objects[1]));
+        let ray = Ray {
+            origin: Point3::new(0., 0., 0.),
+            direction: Vector3::new(0., 0., -1.),
+            time: 0.,
+        };
+        let expected = world.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        let hit = decoded.hit(&ray, 0.001..f64::INFINITY).unwrap();
+        assert_eq!(hit.t, expected.t);
+        assert_eq!(hit.normal, expected.normal);
+    }
+
+    #[test]
+    fn rejects_objects_nested_too_deeply() {
+        // Lists of one list each, never reaching an end.
+        let mut encoder = Encoder::new();
+        for _ in 0..=crate::distributed::wire::MAX_DEPTH {
+            encoder.put(&false).put(&tag::LIST).put(&1usize);
+        }
+        let message = encoder.finish();
+
+        let err = Decoder::new(&message)
+            .get::<Arc<dyn Hittable + Send + Sync>>()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "objects nested too deeply");
+    }
+
+    #[test]
+    fn hits_at_known_distances_and_coordinates() {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let center = Point3::new(0., 0., 0.);
+        let ray = |origin: [f64; 3], direction: [f64; 3]| Ray {
+            origin: origin.into(),
+            direction: direction.into(),
+            time: 0.,
+        };
+
+        // Shape, ray, and distance and texture coordinates of the hit, `NAN` where unchecked.
+        type Case = (
+            &'static str,
+            Arc<dyn Hittable + Send + Sync>,
+            Ray,
+            f64,
+            [f64; 2],
+        );
+        let cases: Vec<Case> = vec![
+            (
+                "sphere",
+                Sphere::new(center, 1., material.clone()),
+                ray([0., 0., 5.], [0., 0., -1.]),
+                4.,
+                [0.25, 0.5],
+            ),
+            (
+                "quad",
+                Quad::new(
+                    Point3::new(-1., -1., 0.),
+                    Vector3::new(2., 0., 0.),
+                    Vector3::new(0., 2., 0.),
+                    material.clone(),
+                ),
+                ray([0.5, 0., 5.], [0., 0., -1.]),
+                5.,
+                [0.75, 0.5],
+            ),
+            (
+                "disk",
+                Disk::new(center, Vector3::unit_z(), 1., material.clone()),
+                ray([0., 0.5, 5.], [0., 0., -1.]),
+                5.,
+                [f64::NAN, 0.5],
+            ),
+            (
+                "triangle",
+                Triangle::new(
+                    center,
+                    Point3::new(1., 0., 0.),
+                    Point3::new(0., 1., 0.),
+                    material.clone(),
+                )
+                .unwrap(),
+                ray([0.25, 0.5, 1.], [0., 0., -1.]),
+                1.,
+                [0.25, 0.5],
+            ),
+            (
+                "cylinder side",
+                Cylinder::new(center, Point3::new(0., 2., 0.), 1., material.clone()),
+                ray([5., 0.5, 0.], [-1., 0., 0.]),
+                4.,
+                [f64::NAN, 0.25],
+            ),
+            (
+                "cylinder cap",
+                Cylinder::new(center, Point3::new(0., 2., 0.), 1., material.clone()),
+                ray([0., 5., 0.5], [0., -1., 0.]),
+                3.,
+                [f64::NAN, 0.5],
+            ),
+            (
+                "cone",
+                Cone::new(center, Point3::new(0., 2., 0.), 1., material.clone()),
+                ray([5., 1., 0.], [-1., 0., 0.]),
+                4.5,
+                [f64::NAN, 0.5],
+            ),
+            (
+                "torus",
+                Torus::new(center, Vector3::unit_y(), 1., 0.25, material),
+                ray([1., 5., 0.], [0., -1., 0.]),
+                4.75,
+                [f64::NAN, 0.75],
+            ),
+        ];
+
+        for (name, shape, ray, t, uv) in cases {
+            let hit = shape.hit(&ray, 0.001..f64::INFINITY).unwrap();
+            assert!((hit.t - t).abs() < 1e-9, "{}: t = {}", name, hit.t);
+            let aabb = shape.bounding_box();
+            for axis in 0..3 {
+                let bounds = aabb.min()[axis]..=aabb.max()[axis];
+                assert!(bounds.contains(&hit.point[axis]), "{}", name);
+            }
+            for (actual, expected) in [hit.u, hit.v].into_iter().zip(uv) {
+                if !expected.is_nan() {
+                    assert!((actual - expected).abs() < 1e-9, "{}: {}", name, actual);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn torus_misses_through_its_hole() {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let torus = Torus::new(
+            Point3::new(0., 0., 0.),
+            Vector3::unit_y(),
+            1.,
+            0.25,
+            material,
+        );
+        let ray = Ray {
+            origin: Point3::new(0., 5., 0.),
+            direction: Vector3::new(0., -1., 0.),
+            time: 0.,
+        };
+
+        assert!(torus.hit(&ray, 0.001..f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn rejects_degenerate_triangles() {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        let a = Point3::new(0., 0., 0.);
+        let on_line = Triangle::new(
+            a,
+            Point3::new(1., 1., 1.),
+            Point3::new(2., 2., 2.),
+            material.clone(),
+        );
+        assert!(on_line.is_none());
+        assert!(Triangle::new(a, a, Point3::new(1., 0., 0.), material.clone()).is_none());
+        let infinite = Point3::new(f64::INFINITY, 0., 0.);
+        assert!(Triangle::new(a, infinite, Point3::new(0., 1., 0.), material).is_none());
+    }
 }
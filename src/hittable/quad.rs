@@ -1,7 +1,12 @@
-use super::{bvh::Aabb, HitPayload, Hittable, HittableList, Range};
-use crate::{material::Material, ray::Ray};
+use super::{bvh::Aabb, tag, HitPayload, Hittable, HittableList, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
 use cgmath::{prelude::*, Point3, Vector3};
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 pub struct Quad {
     q: Point3<f64>,
@@ -14,7 +19,8 @@ pub struct Quad {
 }
 
 impl Hittable for Quad {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload> {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::QuadTests);
         let s = ray.origin - self.q;
         let s1 = ray.direction.cross(self.v);
         let s2 = s.cross(self.u);
@@ -34,7 +40,7 @@ impl Hittable for Quad {
         let beta = rdet * s1.dot(s);
         let gamma = rdet * s2.dot(ray.direction);
 
-        if beta < 0. || beta > 1. || gamma < 0. || gamma > 1. {
+        if !(0. ..=1.).contains(&beta) || !(0. ..=1.).contains(&gamma) {
             return None;
         }
 
@@ -56,6 +62,19 @@ impl Hittable for Quad {
     }
 }
 
+impl EncodeObject for Quad {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::QUAD)
+            .put(&self.q)
+            .put(&self.u)
+            .put(&self.v)
+            .put(&self.normal)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
 impl Quad {
     pub fn new(q: Point3<f64>, u: Vector3<f64>, v: Vector3<f64>, material: Material) -> Arc<Self> {
         let normal = u.cross(v).normalize();
@@ -126,3 +145,17 @@ impl Quad {
         Arc::new(sides)
     }
 }
+
+impl Decode for Quad {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            q: decoder.get()?,
+            u: decoder.get()?,
+            v: decoder.get()?,
+            normal: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
@@ -1,10 +1,16 @@
-use super::{bvh::Aabb, HitPayload, Hittable, Range};
-use crate::{material::Material, ray::Ray};
+use super::{bvh::Aabb, tag, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
 use cgmath::{prelude::*, Point3, Vector3};
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 pub struct Sphere {
     center: Point3<f64>,
+    velocity: Vector3<f64>,
     radius: f64,
     aabb: Aabb,
 
@@ -18,17 +24,45 @@ impl Sphere {
 
         Arc::new(Sphere {
             center,
+            velocity: Vector3::zero(),
             radius,
             aabb,
 
             material,
         })
     }
+
+    /// Sphere moving linearly from `center0` at time 0 to `center1` at time 1.
+    pub fn moving(
+        center0: Point3<f64>,
+        center1: Point3<f64>,
+        radius: f64,
+        material: Material,
+    ) -> Arc<Self> {
+        let rvec = Vector3::from([radius; 3]);
+        let aabb0 = Aabb::from_min_max(center0 - rvec, center0 + rvec);
+        let aabb1 = Aabb::from_min_max(center1 - rvec, center1 + rvec);
+
+        Arc::new(Sphere {
+            center: center0,
+            velocity: center1 - center0,
+            radius,
+            aabb: Aabb::covering(&aabb0, &aabb1),
+
+            material,
+        })
+    }
+
+    fn center(&self, time: f64) -> Point3<f64> {
+        self.center + time.clamp(0., 1.) * self.velocity
+    }
 }
 
 impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload> {
-        let oc = ray.origin - self.center;
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::SphereTests);
+        let center = self.center(ray.time);
+        let oc = ray.origin - center;
         let a = ray.direction.magnitude2();
         let half_b = oc.dot(ray.direction);
         let c = oc.magnitude2() - self.radius.powi(2);
@@ -49,7 +83,7 @@ impl Hittable for Sphere {
         }
 
         let point = ray.at(root);
-        let normal = (point - self.center) / self.radius;
+        let normal = (point - center) / self.radius;
 
         let theta = (-normal.y).acos();
         let phi = (-normal.z).atan2(normal.x) + std::f64::consts::PI;
@@ -58,7 +92,7 @@ impl Hittable for Sphere {
         let v = theta * std::f64::consts::FRAC_1_PI;
 
         Some(HitPayload::new(
-            &ray,
+            ray,
             point,
             normal,
             root,
@@ -72,3 +106,28 @@ impl Hittable for Sphere {
         self.aabb
     }
 }
+
+impl EncodeObject for Sphere {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::SPHERE)
+            .put(&self.center)
+            .put(&self.velocity)
+            .put(&self.radius)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Sphere {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            center: decoder.get()?,
+            velocity: decoder.get()?,
+            radius: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
@@ -0,0 +1,234 @@
+use super::{azimuth, bvh::Aabb, tag, Frame, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use std::{f64::consts::PI, io, sync::Arc};
+
+/// Ring around `axis` whose tube of `minor_radius` follows a circle of `major_radius`.
+///
+/// `u` goes around the axis like on a [`Cylinder`](super::Cylinder), and `v` around the tube
+/// from its innermost line, through the bottom, outermost and top lines.
+pub struct Torus {
+    center: Point3<f64>,
+    frame: Frame,
+    major_radius: f64,
+    minor_radius: f64,
+    aabb: Aabb,
+
+    material: Material,
+}
+
+impl Torus {
+    /// # Panics
+    ///
+    /// If the tube is thicker than the ring, so that the torus would intersect itself.
+    pub fn new(
+        center: Point3<f64>,
+        axis: Vector3<f64>,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Material,
+    ) -> Arc<Self> {
+        assert!(
+            minor_radius <= major_radius,
+            "torus tube cannot be thicker than the ring"
+        );
+
+        let frame = Frame::new(axis);
+        // The circle at the core of the tube, grown by its radius in every direction.
+        let extent = frame
+            .axis
+            .map(|a| major_radius * (1. - a * a).max(0.).sqrt() + minor_radius);
+        let aabb = Aabb::from_min_max(center - extent, center + extent);
+
+        Arc::new(Self {
+            center,
+            frame,
+            major_radius,
+            minor_radius,
+            aabb,
+
+            material,
+        })
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::ShapeTests);
+        let local = self.frame.local_ray(ray, self.center);
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        // Solve along the unit direction from the point of the ray nearest to the center, which
+        // keeps the coefficients small and centers the roots around zero.
+        let length = local.direction.magnitude();
+        let d = local.direction / length;
+        let nearest = -local.origin.to_vec().dot(d);
+        let o = local.origin + nearest * d;
+        let bound = major + minor;
+        if o.to_vec().magnitude2() > bound * bound {
+            return None;
+        }
+
+        // (|p|^2 + R^2 - r^2)^2 = 4 R^2 (x^2 + z^2), with p = o + s d and o perpendicular to d.
+        let e = o.to_vec().magnitude2() + major * major - minor * minor;
+        let r2 = 4. * major * major;
+        let coefficients = [
+            e * e - r2 * (o.x * o.x + o.z * o.z),
+            -2. * r2 * (o.x * d.x + o.z * d.z),
+            2. * e - r2 * (d.x * d.x + d.z * d.z),
+            0.,
+            1.,
+        ];
+
+        // Every crossing lies within the bounding sphere.
+        let start = (range.start * length - nearest).max(-bound);
+        let end = (range.end * length - nearest).min(bound);
+        if start >= end {
+            return None;
+        }
+        let t = real_roots(&coefficients, start, end)
+            .iter()
+            .map(|s| (s + nearest) / length)
+            .find(|t| range.contains(t))?;
+
+        let point = local.at(t);
+        let ring = Vector3::new(point.x, 0., point.z);
+        let core = if ring.magnitude2() > 0. {
+            major * ring.normalize()
+        } else {
+            Vector3::zero()
+        };
+        let normal = (point.to_vec() - core) / minor;
+
+        let around = point.y.atan2(ring.magnitude() - major);
+        let v = (around + PI) * 0.5 * std::f64::consts::FRAC_1_PI;
+
+        Some(HitPayload::new(
+            ray,
+            ray.at(t),
+            self.frame.to_world(normal),
+            t,
+            azimuth(point),
+            v,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl EncodeObject for Torus {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::TORUS)
+            .put(&self.center)
+            .put(&self.frame)
+            .put(&self.major_radius)
+            .put(&self.minor_radius)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Torus {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            center: decoder.get()?,
+            frame: decoder.get()?,
+            major_radius: decoder.get()?,
+            minor_radius: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        })
+    }
+}
+
+const MAX_DEGREE: usize = 4;
+
+/// Real roots in increasing order, at most one per degree of the polynomial.
+#[derive(Default)]
+struct Roots {
+    values: [f64; MAX_DEGREE],
+    len: usize,
+}
+
+impl Roots {
+    fn iter(&self) -> std::slice::Iter<'_, f64> {
+        self.values[..self.len].iter()
+    }
+}
+
+/// Real roots in `start..=end` of the polynomial with `coefficients`, constant term first.
+///
+/// The roots of the derivative split the interval into pieces over which the polynomial is
+/// monotonic, so each piece holds at most one root, found by bisection. Roots where the
+/// polynomial only touches zero, e.g. where a ray grazes the surface, may be missed.
+fn real_roots(coefficients: &[f64], start: f64, end: f64) -> Roots {
+    let mut roots = Roots::default();
+    let degree = coefficients.len() - 1;
+    if degree == 1 {
+        let root = -coefficients[0] / coefficients[1];
+        if (start..=end).contains(&root) {
+            roots.values[0] = root;
+            roots.len = 1;
+        }
+        return roots;
+    }
+
+    let mut derivative = [0.; MAX_DEGREE];
+    for (i, coefficient) in coefficients.iter().enumerate().skip(1) {
+        derivative[i - 1] = i as f64 * coefficient;
+    }
+    let extrema = real_roots(&derivative[..degree], start, end);
+
+    let mut lower = start;
+    for &upper in extrema.iter().chain([end].iter()) {
+        if let Some(root) = bisect(coefficients, lower, upper) {
+            roots.values[roots.len] = root;
+            roots.len += 1;
+        }
+        lower = upper;
+    }
+
+    roots
+}
+
+/// The root in `lower..=upper` of a polynomial that is monotonic there, unless it is at `lower`,
+/// where it belongs to the piece before.
+fn bisect(coefficients: &[f64], mut lower: f64, mut upper: f64) -> Option<f64> {
+    let at_lower = evaluate(coefficients, lower);
+    let at_upper = evaluate(coefficients, upper);
+    if at_upper == 0. {
+        return Some(upper);
+    }
+    if at_lower == 0. || (at_lower < 0.) == (at_upper < 0.) {
+        return None;
+    }
+
+    // Halving the interval as many times as a double has bits leaves it at the float precision.
+    for _ in 0..64 {
+        let middle = 0.5 * (lower + upper);
+        if (evaluate(coefficients, middle) < 0.) == (at_lower < 0.) {
+            lower = middle;
+        } else {
+            upper = middle;
+        }
+    }
+
+    Some(0.5 * (lower + upper))
+}
+
+fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0., |value, &c| value * x + c)
+}
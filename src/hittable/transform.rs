@@ -1,25 +1,53 @@
-use super::{bvh::Aabb, HitPayload, Hittable, Range};
-use crate::ray::Ray;
-use cgmath::{prelude::*, Point3, Quaternion, Vector3};
-use std::sync::Arc;
+use super::{bvh::Aabb, shared_build_time, tag, HitPayload, Hittable, Range, Seen};
+use crate::{
+    distributed::wire::{invalid_data, Decode, Decoder, EncodeObject, Encoder},
+    lerp,
+    ray::Ray,
+};
+use cgmath::{prelude::*, AbsDiffEq, Matrix3, Matrix4, Point3, Quaternion, Vector3, Vector4};
+use std::{io, sync::Arc, time::Duration};
 
+/// Relative size below which a matrix is taken as singular, and difference from `0 0 0 1` below
+/// which its last row is taken as affine, to allow for rounding in matrices from files.
+const TOLERANCE: f64 = 1e-9;
+
+/// Places an object by a linear map such as a scale or shear, followed by a rotation and a
+/// translation, which may move during the exposure.
 pub struct Transform {
-    translation: Vector3<f64>,
-    rotation: Quaternion<f64>,
+    translation: [Vector3<f64>; 2],
+    rotation: [Quaternion<f64>; 2],
+    linear: Matrix3<f64>,
+    inverse: Matrix3<f64>,
     object: Arc<dyn Hittable + Send + Sync>,
+    aabb: Aabb,
 }
 
 impl Hittable for Transform {
-    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload> {
-        let inverse_rot = self.rotation.invert();
-        let origin = inverse_rot.rotate_point(ray.origin - self.translation);
-        let direction = inverse_rot.rotate_vector(ray.direction);
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        let (translation, rotation) = self.at(ray.time);
+
+        // The direction is not normalized, so that `t` means the same point in both spaces.
+        let inverse_rot = rotation.invert();
+        let origin = self.inverse * inverse_rot.rotate_vector((ray.origin - translation).to_vec());
+        let direction = self.inverse * inverse_rot.rotate_vector(ray.direction);
 
-        let equivalent_ray = Ray { origin, direction };
+        let equivalent_ray = Ray {
+            origin: Point3::from_vec(origin),
+            direction,
+            time: ray.time,
+        };
 
         if let Some(mut payload) = self.object.hit(&equivalent_ray, range) {
-            payload.point = self.rotation.rotate_point(payload.point) + self.translation;
-            payload.normal = self.rotation.rotate_vector(payload.normal);
+            // Normals stay perpendicular to the surface under the inverse transpose.
+            let normal_matrix = self.inverse.transpose();
+            let point = self.linear * payload.point.to_vec();
+            payload.point = rotation.rotate_point(Point3::from_vec(point)) + translation;
+            payload.normal = rotation
+                .rotate_vector(normal_matrix * payload.normal)
+                .normalize();
+            payload.shading_normal = rotation
+                .rotate_vector(normal_matrix * payload.shading_normal)
+                .normalize();
 
             Some(payload)
         } else {
@@ -28,11 +56,167 @@ impl Hittable for Transform {
     }
 
     fn bounding_box(&self) -> Aabb {
-        let aabb = self.object.bounding_box();
+        self.aabb
+    }
+
+    fn build_time(&self, seen: &mut Seen) -> Duration {
+        shared_build_time(&self.object, seen)
+    }
+}
+
+impl EncodeObject for Transform {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::TRANSFORM)
+            .put(&self.translation)
+            .put(&self.rotation)
+            .put(&self.linear)
+            .put(&self.object)
+            .put(&self.aabb);
+    }
+}
+
+impl Transform {
+    #[allow(private_bounds)]
+    pub fn new<H: Hittable + Send + Sync + 'static>(
+        object: Arc<H>,
+        translation: Vector3<f64>,
+        rotation: Quaternion<f64>,
+    ) -> Arc<Self> {
+        Self::moving(object, (translation, rotation), (translation, rotation))
+    }
+
+    /// Transform moving from `start` at time 0 to `end` at time 1, interpolating the translation
+    /// linearly and the rotation spherically.
+    #[allow(private_bounds)]
+    pub fn moving<H: Hittable + Send + Sync + 'static>(
+        object: Arc<H>,
+        start: (Vector3<f64>, Quaternion<f64>),
+        end: (Vector3<f64>, Quaternion<f64>),
+    ) -> Arc<Self> {
+        Self::build(object, Matrix3::identity(), start, end).unwrap()
+    }
+
+    /// Transform scaling the object along its axes by `scale` before rotating and translating it,
+    /// or `None` if a component of `scale` is zero.
+    #[allow(private_bounds)]
+    pub fn scaled<H: Hittable + Send + Sync + 'static>(
+        object: Arc<H>,
+        translation: Vector3<f64>,
+        rotation: Quaternion<f64>,
+        scale: Vector3<f64>,
+    ) -> Option<Arc<Self>> {
+        let linear = Matrix3::from_diagonal(scale);
+        Self::build(
+            object,
+            linear,
+            (translation, rotation),
+            (translation, rotation),
+        )
+    }
+
+    /// Transform by an affine `matrix` acting on column vectors, which may scale and shear, or
+    /// `None` if `matrix` is singular or projective, i.e. its last row is not `0 0 0 1`.
+    #[allow(private_bounds)]
+    pub fn from_matrix<H: Hittable + Send + Sync + 'static>(
+        object: Arc<H>,
+        matrix: Matrix4<f64>,
+    ) -> Option<Arc<Self>> {
+        Self::from_matrix_shared(object, matrix)
+    }
+
+    /// [`Transform::from_matrix`] for objects whose type is not known statically.
+    pub(crate) fn from_matrix_shared(
+        object: Arc<dyn Hittable + Send + Sync>,
+        matrix: Matrix4<f64>,
+    ) -> Option<Arc<Self>> {
+        if is_projective(matrix) {
+            return None;
+        }
+
+        let placement = (matrix.w.truncate(), Quaternion::one());
+        Self::build(object, linear_part(matrix), placement, placement)
+    }
+
+    fn build(
+        object: Arc<dyn Hittable + Send + Sync>,
+        linear: Matrix3<f64>,
+        start: (Vector3<f64>, Quaternion<f64>),
+        end: (Vector3<f64>, Quaternion<f64>),
+    ) -> Option<Arc<Self>> {
+        if is_singular(linear) {
+            return None;
+        }
+
+        let inverse = linear.invert()?;
+        let mut transform = Self {
+            translation: [start.0, end.0],
+            rotation: [start.1, end.1],
+            linear,
+            inverse,
+            object,
+            aabb: Aabb::empty(),
+        };
+        transform.aabb = transform.motion_bounds();
+
+        Some(Arc::new(transform))
+    }
+
+    fn is_moving(&self) -> bool {
+        self.translation[0] != self.translation[1] || self.rotation[0] != self.rotation[1]
+    }
+
+    fn at(&self, time: f64) -> (Vector3<f64>, Quaternion<f64>) {
+        if !self.is_moving() {
+            return (self.translation[0], self.rotation[0]);
+        }
+
+        let time = time.clamp(0., 1.);
+        let translation = lerp(self.translation[0], self.translation[1], time);
+        let rotation = self.rotation[0].slerp(self.rotation[1], time);
+
+        (translation, rotation)
+    }
+
+    fn motion_bounds(&self) -> Aabb {
+        const STEPS: u32 = 16;
+
+        let linear = self.linear;
+        let aabb = Self::map_bounds(&self.object.bounding_box(), |point| {
+            Point3::from_vec(linear * point.to_vec())
+        });
+        if !self.is_moving() {
+            return Self::bounds_at(&aabb, self.translation[0], self.rotation[0]);
+        }
+
+        let mut bounds = Aabb::empty();
+        for step in 0..=STEPS {
+            let (translation, rotation) = self.at(step as f64 / STEPS as f64);
+            bounds = Aabb::covering(&bounds, &Self::bounds_at(&aabb, translation, rotation));
+        }
+
+        // Between two samples a corner travels along an arc, never farther than half the arc
+        // length from the nearest sampled position.
+        let angle = 2. * self.rotation[0].dot(self.rotation[1]).abs().min(1.).acos();
+        let radius = aabb.center.to_vec().magnitude() + aabb.half_extents.magnitude();
+        let padding = radius * angle / (2 * STEPS) as f64;
+
+        Aabb {
+            center: bounds.center,
+            half_extents: bounds.half_extents + Vector3::from([padding; 3]),
+        }
+    }
+
+    fn bounds_at(aabb: &Aabb, translation: Vector3<f64>, rotation: Quaternion<f64>) -> Aabb {
+        Self::map_bounds(aabb, |point| rotation.rotate_point(point) + translation)
+    }
+
+    /// Bounds of the corners of `aabb` mapped by the affine `map`.
+    fn map_bounds(aabb: &Aabb, map: impl Fn(Point3<f64>) -> Point3<f64>) -> Aabb {
         let factor = [-1., 1.];
 
-        let mut minimum = Point3::from([std::f64::INFINITY; 3]);
-        let mut maximum = Point3::from([std::f64::NEG_INFINITY; 3]);
+        let mut minimum = Point3::from([f64::INFINITY; 3]);
+        let mut maximum = Point3::from([f64::NEG_INFINITY; 3]);
         for i in factor {
             for j in factor {
                 for k in factor {
@@ -42,7 +226,7 @@ impl Hittable for Transform {
                             j * aabb.half_extents.y,
                             k * aabb.half_extents.z,
                         );
-                    let point = self.rotation.rotate_point(point) + self.translation;
+                    let point = map(point);
 
                     minimum.x = minimum.x.min(point.x);
                     minimum.y = minimum.y.min(point.y);
@@ -59,17 +243,121 @@ impl Hittable for Transform {
     }
 }
 
-impl Transform {
-    #[allow(private_bounds)]
-    pub fn new<H: Hittable + Send + Sync + 'static>(
-        object: Arc<H>,
-        translation: Vector3<f64>,
-        rotation: Quaternion<f64>,
-    ) -> Arc<Self> {
-        Arc::new(Self {
+/// Upper left 3x3 part of `matrix`, which maps directions.
+pub(crate) fn linear_part(matrix: Matrix4<f64>) -> Matrix3<f64> {
+    Matrix3::from_cols(
+        matrix.x.truncate(),
+        matrix.y.truncate(),
+        matrix.z.truncate(),
+    )
+}
+
+/// Whether `linear` collapses space onto a plane or less, up to rounding relative to its scale.
+pub(crate) fn is_singular(linear: Matrix3<f64>) -> bool {
+    let determinant = linear.determinant();
+    let scale = linear.x.magnitude() * linear.y.magnitude() * linear.z.magnitude();
+
+    determinant.is_nan() || determinant.abs() <= TOLERANCE * scale
+}
+
+/// Whether the last row of `matrix` differs from `0 0 0 1` by more than rounding.
+pub(crate) fn is_projective(matrix: Matrix4<f64>) -> bool {
+    !matrix.row(3).abs_diff_eq(&Vector4::unit_w(), TOLERANCE)
+}
+
+impl Decode for Transform {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let translation = decoder.get()?;
+        let rotation = decoder.get()?;
+        let linear: Matrix3<f64> = decoder.get()?;
+        let inverse = Some(linear)
+            .filter(|&linear| !is_singular(linear))
+            .and_then(|linear| linear.invert())
+            .ok_or_else(|| invalid_data("singular transform matrix"))?;
+
+        Ok(Self {
             translation,
             rotation,
-            object,
+            linear,
+            inverse,
+            object: decoder.get()?,
+            aabb: decoder.get()?,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Sphere, material::Material, Color};
+
+    fn sphere() -> Arc<Sphere> {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        Sphere::new(Point3::origin(), 1., material)
+    }
+
+    #[test]
+    fn scaled_sphere_has_ellipsoid_normal() {
+        let ellipsoid = Transform::scaled(
+            sphere(),
+            Vector3::zero(),
+            Quaternion::one(),
+            Vector3::new(2., 1., 1.),
+        )
+        .unwrap();
+        let ray = Ray {
+            origin: Point3::new(5., 0.5, 0.),
+            direction: Vector3::new(-2., 0., 0.),
+            time: 0.,
+        };
+        let payload = ellipsoid.hit(&ray, 0.001..f64::INFINITY).unwrap();
+
+        // x^2 / 4 + y^2 + z^2 = 1, whose gradient is (x / 4, y, z).
+        let x = 3f64.sqrt();
+        assert!((payload.point - Point3::new(x, 0.5, 0.)).magnitude() < 1e-9);
+        assert!((ray.at(payload.t) - payload.point).magnitude() < 1e-9);
+        let normal = Vector3::new(x / 4., 0.5, 0.).normalize();
+        assert!((payload.normal - normal).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn sheared_normal_stays_perpendicular() {
+        let mut matrix = Matrix4::identity();
+        matrix.y.x = 1.;
+        let sheared = Transform::from_matrix(sphere(), matrix).unwrap();
+        let ray = Ray {
+            origin: Point3::new(0.3, 5., 0.2),
+            direction: Vector3::new(0., -1., 0.),
+            time: 0.,
+        };
+        let payload = sheared.hit(&ray, 0.001..f64::INFINITY).unwrap();
+
+        // Tangents of the sphere map to tangents of the sheared surface.
+        let linear = linear_part(matrix);
+        let local = linear.invert().unwrap() * payload.point.to_vec();
+        let tangent = linear * local.cross(Vector3::unit_z());
+        assert!(payload.normal.dot(tangent).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_singular_and_projective_matrices() {
+        let flat = Matrix4::from_nonuniform_scale(1., 0., 1.);
+        assert!(Transform::from_matrix(sphere(), flat).is_none());
+        let scale = Vector3::new(1., 1., 0.);
+        assert!(Transform::scaled(sphere(), Vector3::zero(), Quaternion::one(), scale).is_none());
+
+        let mut projective = Matrix4::identity();
+        projective.x.w = 0.5;
+        assert!(Transform::from_matrix(sphere(), projective).is_none());
+    }
+
+    #[test]
+    fn accepts_rounding_and_small_scales() {
+        let mut rounded = Matrix4::identity();
+        rounded.z.w = 1e-12;
+        assert!(Transform::from_matrix(sphere(), rounded).is_some());
+
+        let tiny = Matrix4::from_scale(1e-4);
+        assert!(Transform::from_matrix(sphere(), tiny).is_some());
+    }
+}
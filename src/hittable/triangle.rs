@@ -0,0 +1,147 @@
+use super::{bvh::Aabb, tag, HitPayload, Hittable, Range};
+use crate::{
+    distributed::wire::{invalid_data, Decode, Decoder, EncodeObject, Encoder},
+    material::Material,
+    ray::Ray,
+    stats::{self, Counter},
+};
+use cgmath::{prelude::*, Point3, Vector3};
+use std::{io, sync::Arc};
+
+/// Single triangle, facing the side from which `a`, `b` and `c` appear counterclockwise. `u` and
+/// `v` are the barycentric weights of `b` and `c`.
+pub struct Triangle {
+    a: Point3<f64>,
+    e1: Vector3<f64>,
+    e2: Vector3<f64>,
+    normal: Vector3<f64>,
+    aabb: Aabb,
+
+    material: Material,
+}
+
+/// Smallest sine of the angle at `a`, below which the edges count as parallel.
+const MIN_SINE: f64 = 1e-9;
+
+impl Triangle {
+    /// Triangle with the corners `a`, `b` and `c`, or `None` if they lie on a line or coincide,
+    /// so that it has no normal.
+    pub fn new(
+        a: Point3<f64>,
+        b: Point3<f64>,
+        c: Point3<f64>,
+        material: Material,
+    ) -> Option<Arc<Self>> {
+        let e1 = b - a;
+        let e2 = c - a;
+        if is_degenerate(e1, e2) {
+            return None;
+        }
+        let normal = e1.cross(e2).normalize();
+
+        let minimum = Point3::new(
+            a.x.min(b.x).min(c.x),
+            a.y.min(b.y).min(c.y),
+            a.z.min(b.z).min(c.z),
+        );
+        let maximum = Point3::new(
+            a.x.max(b.x).max(c.x),
+            a.y.max(b.y).max(c.y),
+            a.z.max(b.z).max(c.z),
+        );
+        let aabb = Aabb::from_min_max(minimum, maximum).padding();
+
+        Some(Arc::new(Self {
+            a,
+            e1,
+            e2,
+            normal,
+            aabb,
+
+            material,
+        }))
+    }
+}
+
+/// Whether the triangle spanned by the edges `e1` and `e2` has no area to speak of.
+fn is_degenerate(e1: Vector3<f64>, e2: Vector3<f64>) -> bool {
+    let area = e1.cross(e2).magnitude();
+    // Also true for corners that are not finite.
+    area.is_nan() || area <= MIN_SINE * e1.magnitude() * e2.magnitude()
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, range: Range<f64>) -> Option<HitPayload<'_>> {
+        stats::count(Counter::TriangleTests);
+
+        let pvec = ray.direction.cross(self.e2);
+        let det = self.e1.dot(pvec);
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+        let inv_det = det.recip();
+
+        let tvec = ray.origin - self.a;
+        let b1 = tvec.dot(pvec) * inv_det;
+        if !(0. ..=1.).contains(&b1) {
+            return None;
+        }
+
+        let qvec = tvec.cross(self.e1);
+        let b2 = ray.direction.dot(qvec) * inv_det;
+        if b2 < 0. || b1 + b2 > 1. {
+            return None;
+        }
+
+        let t = self.e2.dot(qvec) * inv_det;
+        if !range.contains(&t) {
+            return None;
+        }
+
+        Some(HitPayload::new(
+            ray,
+            ray.at(t),
+            self.normal,
+            t,
+            b1,
+            b2,
+            &self.material,
+        ))
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.aabb
+    }
+}
+
+impl EncodeObject for Triangle {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&tag::TRIANGLE)
+            .put(&self.a)
+            .put(&self.e1)
+            .put(&self.e2)
+            .put(&self.normal)
+            .put(&self.aabb)
+            .put(&self.material);
+    }
+}
+
+impl Decode for Triangle {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let triangle = Self {
+            a: decoder.get()?,
+            e1: decoder.get()?,
+            e2: decoder.get()?,
+            normal: decoder.get()?,
+            aabb: decoder.get()?,
+
+            material: decoder.get()?,
+        };
+        if is_degenerate(triangle.e1, triangle.e2) {
+            return Err(invalid_data("degenerate triangle"));
+        }
+
+        Ok(triangle)
+    }
+}
@@ -1,10 +1,23 @@
+pub mod animation;
 pub mod camera;
+pub mod colorspace;
+pub mod distributed;
+pub mod film;
 pub mod hittable;
+pub mod loader;
 pub mod material;
+pub mod post;
+pub mod preview;
+pub mod progress;
+pub mod scene;
+pub mod stats;
 pub mod texture;
+pub mod tonemap;
 
 mod ray;
 
+use colorspace::{srgb_encode, ColorSpace};
+use distributed::wire::{EncodeObject, Encoder};
 use math::{prelude::*, Point3, Vector2, Vector3};
 use rand::Rng;
 use std::ops::{Add, Mul};
@@ -19,25 +32,27 @@ impl Texture for Color {
     }
 }
 
-fn color_to_rgb(color: Color) -> [u8; 3] {
-    const GAMMA: f64 = 2.2;
+impl EncodeObject for Color {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder.put(&texture::tag::COLOR).put(self);
+    }
+}
 
-    let r = (color.x.powf(GAMMA.recip()) * 255.) as u8;
-    let g = (color.y.powf(GAMMA.recip()) * 255.) as u8;
-    let b = (color.z.powf(GAMMA.recip()) * 255.) as u8;
+fn color_to_rgb(color: Color) -> [u8; 3] {
+    let r = (srgb_encode(color.x) * 255. + 0.5) as u8;
+    let g = (srgb_encode(color.y) * 255. + 0.5) as u8;
+    let b = (srgb_encode(color.z) * 255. + 0.5) as u8;
 
     [r, g, b]
 }
 
+/// Linear sRGB color of 8-bit sRGB-encoded values.
 fn rgb_to_color(rgb: [u8; 3]) -> Color {
-    const GAMMA: f64 = 2.2;
-
-    let scale = 255.0_f64.recip();
-    let x = (scale * (rgb[0] as f64)).powf(GAMMA);
-    let y = (scale * (rgb[1] as f64)).powf(GAMMA);
-    let z = (scale * (rgb[2] as f64)).powf(GAMMA);
+    ColorSpace::Rec709.from_srgb8(rgb)
+}
 
-    Color::new(x, y, z)
+fn luminance(color: Color) -> f64 {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
 }
 
 fn random_in_unit_disk() -> Vector2<f64> {
@@ -63,7 +78,7 @@ fn random_in_unit_sphere() -> Vector3<f64> {
 }
 
 fn random_unit_vector() -> Vector3<f64> {
-    return random_in_unit_sphere().normalize();
+    random_in_unit_sphere().normalize()
 }
 
 fn near_zero(vec: &Vector3<f64>) -> bool {
@@ -84,7 +99,7 @@ fn refract(uv: Vector3<f64>, n: Vector3<f64>, etai_over_etat: f64) -> Vector3<f6
     let cos_theta = (-uv.dot(n)).min(1.0);
     let r_out_perp = etai_over_etat * (uv + cos_theta * n);
     let r_out_parallel = -((1.0 - r_out_perp.magnitude2()).abs()).sqrt() * n;
-    return r_out_perp + r_out_parallel;
+    r_out_perp + r_out_parallel
 }
 
 fn lerp<V: Add<Output = V> + Mul<f64, Output = V>>(v0: V, v1: V, t: f64) -> V {
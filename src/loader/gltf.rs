@@ -0,0 +1,522 @@
+use super::invalid_data;
+use crate::{
+    camera::{CameraBuilder, Projection},
+    hittable::{
+        is_projective, is_singular, linear_part, Hittable, HittableList, MeshFace, Sphere,
+        Transform, TriangleMesh, VertexBuffers,
+    },
+    material::Material,
+    texture::ImageTexture,
+    Color,
+};
+use ::gltf::{
+    buffer,
+    camera::Projection as GltfProjection,
+    image::{Data as ImageData, Format},
+    khr_lights_punctual::{Kind, Light},
+    mesh::Mode,
+    Camera, Mesh, Node,
+};
+use ::image::{DynamicImage, ImageBuffer};
+use cgmath::{prelude::*, Matrix4, Point3, Vector2, Vector3, Vector4};
+use std::{collections::HashMap, f64::consts::PI, io, sync::Arc};
+
+/// Reads glTF 2.0 scenes, both `.gltf` with their external or embedded resources and `.glb`.
+///
+/// The node hierarchy becomes nested [`Transform`]s, each mesh a [`TriangleMesh`] shared by all
+/// nodes instancing it. Metallic-roughness materials are approximated by the closest material
+/// of the crate: transmissive ones become dielectrics, emissive ones lights, mostly metallic ones
+/// metals with the roughness as fuzz and all others lambertians, using the base color texture
+/// tinted by the base color factor when there is one. Nodes scaled to nothing, which hides them,
+/// are left out.
+///
+/// Point and spot lights become small spheres emitting the light's intensity in all directions.
+/// Directional lights have no position to place an emitter at and are skipped. Both are reported
+/// in [`GltfScene::warnings`].
+pub struct GltfLoader {
+    light_radius: f64,
+}
+
+/// Content of the default scene of a glTF file.
+pub struct GltfScene {
+    pub world: HittableList,
+    /// Cameras in the order their nodes are visited, looking down their local -Z axis.
+    pub cameras: Vec<CameraBuilder>,
+    /// Parts of the file that were skipped or only approximated.
+    pub warnings: Vec<String>,
+}
+
+/// State of one [`GltfLoader::load`], caching what nodes share.
+struct Importer<'a> {
+    loader: &'a GltfLoader,
+    buffers: &'a [buffer::Data],
+    images: &'a [ImageData],
+    node_count: usize,
+
+    meshes: HashMap<usize, Option<Arc<TriangleMesh>>>,
+    materials: HashMap<Option<usize>, Material>,
+    decoded: HashMap<usize, Option<Arc<DynamicImage>>>,
+    cameras: Vec<CameraBuilder>,
+    warnings: Vec<String>,
+}
+
+impl Default for GltfLoader {
+    fn default() -> Self {
+        Self { light_radius: 0.05 }
+    }
+}
+
+impl GltfLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Radius of the spheres standing in for point and spot lights.
+    #[inline]
+    pub fn light_radius(&mut self, light_radius: f64) -> &mut Self {
+        self.light_radius = light_radius;
+        self
+    }
+
+    pub fn load(&self, path: &str) -> io::Result<GltfScene> {
+        let (document, buffers, images) = ::gltf::import(path).map_err(|err| match err {
+            ::gltf::Error::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        })?;
+
+        let scene = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+            .ok_or_else(|| invalid_data("no scene"))?;
+
+        let mut importer = Importer {
+            loader: self,
+            buffers: &buffers,
+            images: &images,
+            node_count: document.nodes().len(),
+
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            decoded: HashMap::new(),
+            cameras: vec![],
+            warnings: vec![],
+        };
+
+        let mut world = HittableList::new();
+        for node in scene.nodes() {
+            if let Some(object) = importer.node(&node, Matrix4::identity(), 0)? {
+                world.push_shared(object);
+            }
+        }
+
+        Ok(GltfScene {
+            world,
+            cameras: importer.cameras,
+            warnings: importer.warnings,
+        })
+    }
+}
+
+impl Importer<'_> {
+    /// Object of `node` and its descendants, in the frame of its parent, which `parent` maps to
+    /// the world frame.
+    fn node(
+        &mut self,
+        node: &Node,
+        parent: Matrix4<f64>,
+        depth: usize,
+    ) -> io::Result<Option<Arc<dyn Hittable + Send + Sync>>> {
+        // A node hierarchy deeper than the number of nodes must be cyclic.
+        if depth > self.node_count {
+            return Err(invalid_data("cyclic node hierarchy"));
+        }
+
+        let matrix = Matrix4::from(
+            node.transform()
+                .matrix()
+                .map(|column| column.map(f64::from)),
+        );
+        if is_singular(linear_part(matrix)) {
+            return Ok(None);
+        }
+        if is_projective(matrix) {
+            return Err(invalid_data("projective node matrix"));
+        }
+        let placement = parent * matrix;
+
+        if let Some(camera) = node.camera() {
+            self.cameras.push(camera_builder(&camera, placement));
+        }
+
+        let mut objects = HittableList::new();
+        if let Some(mesh) = node.mesh() {
+            if let Some(mesh) = self.mesh(&mesh)? {
+                objects.push(mesh);
+            }
+        }
+        if let Some(light) = node.light() {
+            if let Some(light) = self.light(&light) {
+                objects.push(light);
+            }
+        }
+        for child in node.children() {
+            if let Some(child) = self.node(&child, placement, depth + 1)? {
+                objects.push_shared(child);
+            }
+        }
+
+        let Some(object) = objects.into_shared() else {
+            return Ok(None);
+        };
+        if matrix == Matrix4::identity() {
+            return Ok(Some(object));
+        }
+
+        // The checks above leave only matrices a transform can be built from.
+        Ok(Transform::from_matrix_shared(object, matrix).map(|transform| transform as _))
+    }
+
+    /// All triangle primitives of `mesh` merged into one mesh, or `None` if it has none.
+    fn mesh(&mut self, mesh: &Mesh) -> io::Result<Option<Arc<TriangleMesh>>> {
+        if let Some(found) = self.meshes.get(&mesh.index()) {
+            return Ok(found.clone());
+        }
+
+        let mut buffers = VertexBuffers::default();
+        let mut faces = vec![];
+        let mut materials = vec![];
+
+        let data = self.buffers;
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(|buffer| Some(data[buffer.index()].0.as_slice()));
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+
+            let position_base = buffers.positions.len() as u32;
+            buffers
+                .positions
+                .extend(positions.map(|p| Point3::from(p.map(f64::from))));
+            let count = buffers.positions.len() - position_base as usize;
+
+            // Attributes of another length than the positions are malformed and left out.
+            let normals: Option<Vec<_>> = reader
+                .read_normals()
+                .map(|normals| normals.map(|n| Vector3::from(n.map(f64::from))).collect());
+            let normal_base = buffers.normals.len() as u32;
+            let normals = normals.filter(|normals| normals.len() == count);
+            let has_normals = normals.is_some();
+            buffers.normals.extend(normals.into_iter().flatten());
+
+            // glTF puts the origin of texture space at the top left, textures here at the bottom.
+            let uvs: Option<Vec<_>> = reader.read_tex_coords(0).map(|uvs| {
+                uvs.into_f32()
+                    .map(|[u, v]| Vector2::new(u as f64, 1. - v as f64))
+                    .collect()
+            });
+            let uv_base = buffers.uvs.len() as u32;
+            let uvs = uvs.filter(|uvs| uvs.len() == count);
+            let has_uvs = uvs.is_some();
+            buffers.uvs.extend(uvs.into_iter().flatten());
+
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..count as u32).collect(),
+            };
+            if indices.iter().any(|&index| index as usize >= count) {
+                return Err(invalid_data("vertex index out of range"));
+            }
+
+            let triangles = match primitive.mode() {
+                Mode::Triangles => indices
+                    .chunks_exact(3)
+                    .map(|t| [t[0], t[1], t[2]])
+                    .collect(),
+                // Every other triangle of a strip is wound the other way round.
+                Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+                    .map(|i| match i % 2 {
+                        0 => [indices[i], indices[i + 1], indices[i + 2]],
+                        _ => [indices[i + 1], indices[i], indices[i + 2]],
+                    })
+                    .collect(),
+                Mode::TriangleFan => (1..indices.len().saturating_sub(1))
+                    .map(|i| [indices[0], indices[i], indices[i + 1]])
+                    .collect(),
+                Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => vec![],
+            };
+            if triangles.is_empty() {
+                continue;
+            }
+
+            let material = materials.len() as u32;
+            materials.push(self.material(&primitive.material()));
+            faces.extend(triangles.into_iter().map(|corners: [u32; 3]| MeshFace {
+                positions: corners.map(|i| position_base + i),
+                normals: has_normals.then(|| corners.map(|i| normal_base + i)),
+                uvs: has_uvs.then(|| corners.map(|i| uv_base + i)),
+                colors: None,
+                material,
+            }));
+        }
+
+        let mesh_object =
+            (!faces.is_empty()).then(|| TriangleMesh::new(Arc::new(buffers), faces, materials));
+        self.meshes.insert(mesh.index(), mesh_object.clone());
+
+        Ok(mesh_object)
+    }
+
+    fn material(&mut self, material: &::gltf::Material) -> Material {
+        if let Some(found) = self.materials.get(&material.index()) {
+            return found.clone();
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _alpha] = pbr.base_color_factor().map(f64::from);
+        let base_color = Color::new(r, g, b);
+        let emission = Color::from(material.emissive_factor().map(f64::from))
+            * material.emissive_strength().unwrap_or(1.) as f64;
+        let transmission = material
+            .transmission()
+            .map_or(0., |transmission| transmission.transmission_factor());
+
+        let converted = if emission != Color::zero() {
+            Material::diffuse_light(Arc::new(emission))
+        } else if transmission >= 0.5 {
+            Material::dielectric(material.ior().unwrap_or(1.5) as f64)
+        } else if pbr.metallic_factor() >= 0.5 {
+            Material::metal(base_color, pbr.roughness_factor() as f64)
+        } else {
+            let image = pbr
+                .base_color_texture()
+                .and_then(|info| self.image(info.texture().source().index()));
+            match image {
+                Some(image) => {
+                    Material::lambertian(Arc::new(ImageTexture::from_image(image, base_color)))
+                }
+                None => Material::lambertian(Arc::new(base_color)),
+            }
+        };
+
+        self.materials.insert(material.index(), converted.clone());
+        converted
+    }
+
+    /// Adds `warning` unless an instance of the same object already did.
+    fn warn(&mut self, warning: String) {
+        if !self.warnings.contains(&warning) {
+            self.warnings.push(warning);
+        }
+    }
+
+    /// Decoded image, or `None` if its pixel format cannot be read.
+    fn image(&mut self, image: usize) -> Option<Arc<DynamicImage>> {
+        self.decoded
+            .entry(image)
+            .or_insert_with(|| Some(Arc::new(dynamic_image(self.images.get(image)?)?)))
+            .clone()
+    }
+
+    fn light(&mut self, light: &Light) -> Option<Arc<Sphere>> {
+        let name = light.name().unwrap_or("unnamed");
+        match light.kind() {
+            Kind::Directional => {
+                self.warn(format!(
+                    "skipped directional light {} ({name})",
+                    light.index()
+                ));
+                return None;
+            }
+            Kind::Spot { .. } => self.warn(format!(
+                "spot light {} ({name}) emits in all directions",
+                light.index()
+            )),
+            Kind::Point => {}
+        }
+
+        // A sphere of radiance L appears from every direction as a disk of area pi r^2, so it
+        // has an intensity of L pi r^2.
+        let radius = self.loader.light_radius;
+        let intensity = Color::from(light.color().map(f64::from)) * light.intensity() as f64;
+        let radiance = intensity / (PI * radius * radius);
+
+        Some(Sphere::new(
+            Point3::origin(),
+            radius,
+            Material::diffuse_light(Arc::new(radiance)),
+        ))
+    }
+}
+
+/// Camera at the node that `placement` maps to the world frame.
+fn camera_builder(camera: &Camera, placement: Matrix4<f64>) -> CameraBuilder {
+    let mut builder = CameraBuilder::default();
+    let lookfrom = Point3::from_vec(placement.w.truncate());
+    let forward = (placement * -Vector4::unit_z()).truncate();
+    builder
+        .lookfrom(lookfrom)
+        .lookat(lookfrom + forward.normalize())
+        .vup((placement * Vector4::unit_y()).truncate());
+
+    match camera.projection() {
+        GltfProjection::Perspective(perspective) => {
+            builder.vfov((perspective.yfov() as f64).to_degrees());
+            if let Some(aspect_ratio) = perspective.aspect_ratio() {
+                let height = builder.image_width as f64 / aspect_ratio as f64;
+                builder.image_height(height.round().max(1.) as u32);
+            }
+        }
+        GltfProjection::Orthographic(orthographic) => {
+            let (xmag, ymag) = (orthographic.xmag() as f64, orthographic.ymag() as f64);
+            builder.projection(Projection::Orthographic {
+                view_width: 2. * xmag,
+            });
+            if xmag > 0. && ymag > 0. {
+                let height = builder.image_width as f64 * ymag / xmag;
+                builder.image_height(height.round().max(1.) as u32);
+            }
+        }
+    }
+
+    builder
+}
+
+/// Image of decoded pixel data, whose 16 and 32 bit channels are in native byte order.
+fn dynamic_image(data: &ImageData) -> Option<DynamicImage> {
+    let (width, height) = (data.width, data.height);
+    let pixels = &data.pixels;
+    let wide = || -> Vec<u16> {
+        pixels
+            .chunks_exact(2)
+            .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+            .collect()
+    };
+    let float = || -> Vec<f32> {
+        pixels
+            .chunks_exact(4)
+            .map(|c| f32::from_ne_bytes([c[0], c[1], c[2], c[3]]))
+            .collect()
+    };
+
+    match data.format {
+        Format::R8 => {
+            ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageLuma8)
+        }
+        Format::R8G8 => {
+            ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageLumaA8)
+        }
+        Format::R8G8B8 => {
+            ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageRgb8)
+        }
+        Format::R8G8B8A8 => {
+            ImageBuffer::from_raw(width, height, pixels.clone()).map(DynamicImage::ImageRgba8)
+        }
+        Format::R16 => ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLuma16),
+        Format::R16G16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageLumaA16)
+        }
+        Format::R16G16B16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgb16)
+        }
+        Format::R16G16B16A16 => {
+            ImageBuffer::from_raw(width, height, wide()).map(DynamicImage::ImageRgba16)
+        }
+        Format::R32G32B32FLOAT => {
+            ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgb32F)
+        }
+        Format::R32G32B32A32FLOAT => {
+            ImageBuffer::from_raw(width, height, float()).map(DynamicImage::ImageRgba32F)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray::Ray;
+    use ::image::{Rgb, RgbImage};
+    use std::fs;
+
+    const SCENE: &str = r#"{
+        "asset": {"version": "2.0"},
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": {"KHR_lights_punctual": {"lights": [
+            {"type": "directional", "name": "sun"},
+            {"type": "spot", "name": "lamp", "spot": {"outerConeAngle": 0.5}}
+        ]}},
+        "scene": 0,
+        "scenes": [{"nodes": [0, 1, 2, 3]}],
+        "nodes": [
+            {"mesh": 0},
+            {"extensions": {"KHR_lights_punctual": {"light": 0}}},
+            {"translation": [0, 2, 0], "extensions": {"KHR_lights_punctual": {"light": 1}}},
+            {"translation": [0, 3, 0], "extensions": {"KHR_lights_punctual": {"light": 1}}}
+        ],
+        "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "TEXCOORD_0": 1}, "material": 0}]}],
+        "materials": [{"pbrMetallicRoughness": {
+            "baseColorFactor": [0.5, 0.25, 1, 1],
+            "baseColorTexture": {"index": 0},
+            "metallicFactor": 0
+        }}],
+        "textures": [{"source": 0}],
+        "images": [{"uri": "white.png"}],
+        "buffers": [{"uri": "triangle.bin", "byteLength": 60}],
+        "bufferViews": [
+            {"buffer": 0, "byteOffset": 0, "byteLength": 36},
+            {"buffer": 0, "byteOffset": 36, "byteLength": 24}
+        ],
+        "accessors": [
+            {"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+             "min": [0, 0, 0], "max": [1, 1, 0]},
+            {"bufferView": 1, "componentType": 5126, "count": 3, "type": "VEC2"}
+        ]
+    }"#;
+
+    /// Loads the scene from files written to a directory of its own for the test `name`.
+    fn load_scene(name: &str) -> GltfScene {
+        let dir = std::env::temp_dir().join(format!("gltf-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let floats = [0., 0., 0., 1., 0., 0., 0., 1., 0., 0., 1., 1., 1., 0., 0.];
+        let bytes: Vec<u8> = floats.iter().flat_map(|f: &f32| f.to_le_bytes()).collect();
+        fs::write(dir.join("triangle.bin"), bytes).unwrap();
+        RgbImage::from_pixel(1, 1, Rgb([255, 255, 255]))
+            .save(dir.join("white.png"))
+            .unwrap();
+        fs::write(dir.join("scene.gltf"), SCENE).unwrap();
+
+        let scene = GltfLoader::new()
+            .load(dir.join("scene.gltf").to_str().unwrap())
+            .unwrap();
+        fs::remove_dir_all(dir).unwrap();
+        scene
+    }
+
+    #[test]
+    fn tints_base_color_texture_by_factor() {
+        let scene = load_scene("tint");
+        let ray = Ray {
+            origin: Point3::new(0.25, 0.25, 1.),
+            direction: Vector3::new(0., 0., -1.),
+            time: 0.,
+        };
+        let payload = scene.world.hit(&ray, 0.001..f64::INFINITY).unwrap();
+
+        let albedo = payload.material.albedo(&payload);
+        assert!((albedo - Color::new(0.5, 0.25, 1.)).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn warns_about_unsupported_lights_once() {
+        let scene = load_scene("lights");
+        assert_eq!(
+            scene.warnings,
+            [
+                "skipped directional light 0 (sun)",
+                "spot light 1 (lamp) emits in all directions",
+            ]
+        );
+        // The mesh and both instances of the spot light.
+        assert_eq!(scene.world.objects.len(), 3);
+    }
+}
@@ -0,0 +1,24 @@
+//! Readers of mesh and scene files.
+
+mod gltf;
+mod obj;
+mod ply;
+
+pub use self::gltf::{GltfLoader, GltfScene};
+pub use obj::ObjLoader;
+pub use ply::PlyLoader;
+
+use std::io;
+
+/// Error for malformed input without a line to point at, e.g. in binary files.
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Error for malformed input at `line`.
+fn parse_error(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {}: {}", line, message),
+    )
+}
@@ -0,0 +1,348 @@
+use super::parse_error;
+use crate::{
+    hittable::{MeshFace, TriangleMesh, VertexBuffers},
+    material::Material,
+};
+use cgmath::{prelude::*, Point3, Vector2, Vector3};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    sync::Arc,
+};
+
+/// Reads Wavefront OBJ files into a single [`TriangleMesh`].
+///
+/// Positions, normals and texture coordinates are read, polygons are triangulated, and the
+/// `usemtl` statements pick the materials registered under their names. Faces without a known
+/// material use the default one; material libraries are not read.
+pub struct ObjLoader {
+    materials: HashMap<String, Material>,
+    default_material: Material,
+}
+
+/// Corner of a face: indices into the positions, UVs and normals.
+#[derive(Clone, Copy)]
+struct Corner {
+    position: u32,
+    uv: Option<u32>,
+    normal: Option<u32>,
+}
+
+impl ObjLoader {
+    pub fn new(default_material: Material) -> Self {
+        Self {
+            materials: HashMap::new(),
+            default_material,
+        }
+    }
+
+    /// Uses `material` for the faces following `usemtl name`.
+    #[inline]
+    pub fn material(&mut self, name: &str, material: Material) -> &mut Self {
+        self.materials.insert(name.to_owned(), material);
+        self
+    }
+
+    pub fn load(&self, path: &str) -> io::Result<Arc<TriangleMesh>> {
+        self.read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(&self, reader: R) -> io::Result<Arc<TriangleMesh>> {
+        let mut buffers = VertexBuffers::default();
+        let mut faces = vec![];
+
+        // Only the materials that are used end up in the mesh, the default one first.
+        let mut materials = vec![self.default_material.clone()];
+        let mut material_indices = HashMap::new();
+        let mut material = 0;
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or_default();
+            let error = |message: &str| parse_error(number + 1, message);
+
+            let mut tokens = line.split_whitespace();
+            let Some(keyword) = tokens.next() else {
+                continue;
+            };
+
+            match keyword {
+                "v" => {
+                    let [x, y, z] = floats(&mut tokens).map_err(error)?;
+                    buffers.positions.push(Point3::new(x, y, z));
+                }
+                "vn" => {
+                    let [x, y, z] = floats(&mut tokens).map_err(error)?;
+                    buffers.normals.push(Vector3::new(x, y, z));
+                }
+                "vt" => {
+                    // The v coordinate is optional and defaults to 0.
+                    let [u] = floats(&mut tokens).map_err(error)?;
+                    let v = match tokens.next() {
+                        Some(v) => v.parse().map_err(|_| error("invalid number"))?,
+                        None => 0.,
+                    };
+                    buffers.uvs.push(Vector2::new(u, v));
+                }
+                "f" => {
+                    let corners = tokens
+                        .map(|token| corner(token, &buffers))
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(error)?;
+                    if corners.len() < 3 {
+                        return Err(error("face with fewer than 3 vertices"));
+                    }
+
+                    let points: Vec<_> = corners
+                        .iter()
+                        .map(|corner| buffers.positions[corner.position as usize])
+                        .collect();
+                    for [a, b, c] in triangulate(&points) {
+                        let corners = [corners[a], corners[b], corners[c]];
+                        faces.push(MeshFace {
+                            positions: corners.map(|corner| corner.position),
+                            normals: all(corners.map(|corner| corner.normal)),
+                            uvs: all(corners.map(|corner| corner.uv)),
+                            colors: None,
+                            material,
+                        });
+                    }
+                }
+                "usemtl" => {
+                    let name = line.trim_start()["usemtl".len()..].trim();
+                    material = match self.materials.get(name) {
+                        Some(found) => {
+                            *material_indices.entry(name.to_owned()).or_insert_with(|| {
+                                materials.push(found.clone());
+                                materials.len() as u32 - 1
+                            })
+                        }
+                        None => 0,
+                    };
+                }
+                // Groups, objects, smoothing groups, libraries, lines and points.
+                _ => {}
+            }
+        }
+
+        Ok(TriangleMesh::new(Arc::new(buffers), faces, materials))
+    }
+}
+
+/// Parses the next `N` tokens as numbers.
+fn floats<'a, const N: usize>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<[f64; N], &'static str> {
+    let mut values = [0.; N];
+    for value in &mut values {
+        *value = tokens
+            .next()
+            .ok_or("missing coordinate")?
+            .parse()
+            .map_err(|_| "invalid number")?;
+    }
+
+    Ok(values)
+}
+
+/// Parses a corner of the form `v`, `v/vt`, `v//vn` or `v/vt/vn`, where negative indices count
+/// back from the last element read so far.
+fn corner(token: &str, buffers: &VertexBuffers) -> Result<Corner, &'static str> {
+    let index = |value: &str, len: usize| -> Result<u32, &'static str> {
+        let value: i64 = value.parse().map_err(|_| "invalid index")?;
+        let index = match value {
+            1.. => value - 1,
+            ..=-1 => len as i64 + value,
+            0 => return Err("invalid index 0"),
+        };
+        if !(0..len as i64).contains(&index) {
+            return Err("index out of range");
+        }
+
+        Ok(index as u32)
+    };
+
+    let mut parts = token.split('/');
+    let position = index(parts.next().unwrap_or_default(), buffers.positions.len())?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(index(uv, buffers.uvs.len())?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(index(normal, buffers.normals.len())?),
+        _ => None,
+    };
+
+    Ok(Corner {
+        position,
+        uv,
+        normal,
+    })
+}
+
+/// Attribute indices of a triangle, if all of its corners have the attribute.
+fn all(indices: [Option<u32>; 3]) -> Option<[u32; 3]> {
+    Some([indices[0]?, indices[1]?, indices[2]?])
+}
+
+/// Splits a planar polygon into triangles by ear clipping, so concave polygons come out right.
+/// Degenerate remainders are fanned.
+pub(super) fn triangulate(points: &[Point3<f64>]) -> Vec<[usize; 3]> {
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Project onto the plane of the polygon, dropping the dominant axis of its Newell normal.
+    let normal = (0..points.len()).fold(Vector3::zero(), |normal, i| {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        normal
+            + Vector3::new(
+                (a.y - b.y) * (a.z + b.z),
+                (a.z - b.z) * (a.x + b.x),
+                (a.x - b.x) * (a.y + b.y),
+            )
+    });
+    let (x, y, orientation) =
+        if normal.x.abs() >= normal.y.abs() && normal.x.abs() >= normal.z.abs() {
+            (1, 2, normal.x)
+        } else if normal.y.abs() >= normal.z.abs() {
+            (2, 0, normal.y)
+        } else {
+            (0, 1, normal.z)
+        };
+    // Mirroring one axis of a polygon seen from behind turns it counterclockwise.
+    let projected: Vec<_> = points
+        .iter()
+        .map(|p| Vector2::new(p[x], p[y] * orientation.signum()))
+        .collect();
+
+    let cross = |o: usize, a: usize, b: usize| {
+        let oa = projected[a] - projected[o];
+        let ob = projected[b] - projected[o];
+        oa.perp_dot(ob)
+    };
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    'clip: while remaining.len() > 3 {
+        let n = remaining.len();
+        for k in 0..n {
+            let (a, b, c) = (
+                remaining[(k + n - 1) % n],
+                remaining[k],
+                remaining[(k + 1) % n],
+            );
+            if cross(a, b, c) <= 0. {
+                continue;
+            }
+
+            let inside = remaining.iter().any(|&p| {
+                p != a
+                    && p != b
+                    && p != c
+                    && cross(a, b, p) >= 0.
+                    && cross(b, c, p) >= 0.
+                    && cross(c, a, p) >= 0.
+            });
+            if !inside {
+                triangles.push([a, b, c]);
+                remaining.remove(k);
+                continue 'clip;
+            }
+        }
+
+        break;
+    }
+
+    for k in 1..remaining.len() - 1 {
+        triangles.push([remaining[0], remaining[k], remaining[k + 1]]);
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// L-shaped hexagon, concave at (1, 1), counterclockwise in the xy plane.
+    const L: [[f64; 2]; 6] = [[0., 0.], [2., 0.], [2., 1.], [1., 1.], [1., 2.], [0., 2.]];
+
+    fn assert_covers_l(points: &[Point3<f64>]) {
+        let triangles = triangulate(points);
+        assert_eq!(triangles.len(), 4);
+
+        let mut area = 0.;
+        for [a, b, c] in triangles {
+            let (a, b, c) = (points[a], points[b], points[c]);
+            area += 0.5 * (b - a).cross(c - a).magnitude();
+
+            let centroid = (a.to_vec() + b.to_vec() + c.to_vec()) / 3.;
+            let inside =
+                (centroid.x < 2. && centroid.y < 1.) || (centroid.x < 1. && centroid.y < 2.);
+            assert!(
+                inside,
+                "triangle {:?} {:?} {:?} is outside the polygon",
+                a, b, c
+            );
+        }
+        assert!((area - 3.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn triangulates_concave_polygon_in_both_windings() {
+        let counterclockwise: Vec<_> = L.iter().map(|&[x, y]| Point3::new(x, y, 0.)).collect();
+        let clockwise: Vec<_> = counterclockwise.iter().rev().copied().collect();
+
+        assert_covers_l(&counterclockwise);
+        assert_covers_l(&clockwise);
+    }
+
+    #[test]
+    fn triangulates_polygon_facing_each_axis() {
+        for axis in 0..3 {
+            let points: Vec<_> = L
+                .iter()
+                .rev()
+                .map(|&[x, y]| {
+                    let mut p = [x, y, 0.];
+                    p.rotate_right(axis);
+                    Point3::from(p)
+                })
+                .collect();
+            let triangles = triangulate(&points);
+            let area: f64 = triangles
+                .iter()
+                .map(|&[a, b, c]| {
+                    0.5 * (points[b] - points[a])
+                        .cross(points[c] - points[a])
+                        .magnitude()
+                })
+                .sum();
+            assert!((area - 3.).abs() < 1e-12, "axis {}", axis);
+        }
+    }
+
+    #[test]
+    fn reads_concave_face_listed_clockwise() {
+        let obj = "v 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 1 0\nv 1 2 0\nv 0 2 0\nf 6 5 4 3 2 1\n";
+        let material = Material::lambertian(crate::Color::new(0.5, 0.5, 0.5).into());
+        let mesh = ObjLoader::new(material).read(obj.as_bytes()).unwrap();
+        assert_eq!(mesh.len(), 4);
+
+        // Straight down through the arms of the L, and through the notch between them.
+        let hits = |x, y| {
+            let ray = crate::ray::Ray {
+                origin: Point3::new(x, y, 1.),
+                direction: Vector3::new(0., 0., -1.),
+                time: 0.,
+            };
+            crate::hittable::Hittable::hit(&*mesh, &ray, 0.001..f64::INFINITY).is_some()
+        };
+        assert!(hits(1.5, 0.5));
+        assert!(hits(0.5, 1.5));
+        assert!(!hits(1.5, 1.5));
+    }
+}
@@ -0,0 +1,593 @@
+use super::{invalid_data, obj::triangulate, parse_error};
+use crate::{
+    colorspace::srgb_decode,
+    hittable::{MeshFace, TriangleMesh, VertexBuffers},
+    material::Material,
+    Color,
+};
+use cgmath::{Point3, Vector2, Vector3};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    sync::Arc,
+};
+
+/// Reads PLY files, ASCII or binary in either byte order, into a single [`TriangleMesh`].
+///
+/// Vertices provide positions and, when present, normals (`nx`, `ny`, `nz`), texture coordinates
+/// (`u`, `v` or `s`, `t`) and sRGB colors (`red`, `green`, `blue`), which a
+/// [`VertexColorTexture`] in the material interpolates. Polygons are triangulated; other elements
+/// and properties are skipped.
+///
+/// [`VertexColorTexture`]: crate::texture::VertexColorTexture
+pub struct PlyLoader {
+    material: Material,
+}
+
+#[derive(Clone, Copy)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Scalar(Scalar),
+    List { count: Scalar, item: Scalar },
+}
+
+struct Property {
+    name: String,
+    kind: Kind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// Vertex properties that are read, in the order of [`Slot::NAMES`].
+#[derive(Clone, Copy, PartialEq)]
+enum Slot {
+    X,
+    Y,
+    Z,
+    Nx,
+    Ny,
+    Nz,
+    U,
+    V,
+    Red,
+    Green,
+    Blue,
+}
+
+/// Reader of the values following the header.
+struct Body<R> {
+    reader: R,
+    format: Format,
+    /// Current line of an ASCII body and the position of its next token.
+    line: String,
+    offset: usize,
+    line_number: usize,
+}
+
+/// Most elements allocated for up front, so that a corrupt count fails reading rather than
+/// allocating.
+const MAX_RESERVE: usize = 1 << 24;
+
+impl PlyLoader {
+    /// Loader giving all faces `material`, e.g. a lambertian with a vertex color texture.
+    pub fn new(material: Material) -> Self {
+        Self { material }
+    }
+
+    pub fn load(&self, path: &str) -> io::Result<Arc<TriangleMesh>> {
+        self.read(BufReader::new(File::open(path)?))
+    }
+
+    pub fn read<R: BufRead>(&self, mut reader: R) -> io::Result<Arc<TriangleMesh>> {
+        let (format, elements, header_lines) = header(&mut reader)?;
+        let mut body = Body {
+            reader,
+            format,
+            line: String::new(),
+            offset: 0,
+            line_number: header_lines,
+        };
+
+        let mut buffers = VertexBuffers::default();
+        let mut has_normals = false;
+        let mut has_uvs = false;
+        let mut has_colors = false;
+        // Corners of all polygons one after another, and the number of corners of each.
+        let mut corners = vec![];
+        let mut sizes = vec![];
+
+        for element in &elements {
+            match element.name.as_str() {
+                "vertex" => {
+                    let slots: Vec<_> = element
+                        .properties
+                        .iter()
+                        .map(|property| Slot::of(&property.name))
+                        .collect();
+                    let present =
+                        |wanted: &[Slot]| wanted.iter().all(|&slot| slots.contains(&Some(slot)));
+                    if !present(&[Slot::X, Slot::Y, Slot::Z]) {
+                        return Err(invalid_data("vertex element without x, y and z"));
+                    }
+                    has_normals = present(&[Slot::Nx, Slot::Ny, Slot::Nz]);
+                    has_uvs = present(&[Slot::U, Slot::V]);
+                    has_colors = present(&[Slot::Red, Slot::Green, Slot::Blue]);
+
+                    buffers.positions.reserve(element.count.min(MAX_RESERVE));
+                    let mut values = [0.; Slot::NAMES.len()];
+                    for _ in 0..element.count {
+                        for (property, slot) in element.properties.iter().zip(&slots) {
+                            match (property.kind, slot) {
+                                (Kind::Scalar(scalar), Some(slot)) => {
+                                    values[*slot as usize] =
+                                        body.value(scalar)? / scalar.color_scale(*slot);
+                                }
+                                (kind, _) => body.skip(kind)?,
+                            }
+                        }
+                        self.push_vertex(&mut buffers, &values, has_normals, has_uvs, has_colors);
+                    }
+                }
+                "face" => {
+                    sizes.reserve(element.count.min(MAX_RESERVE));
+                    corners.reserve(3 * element.count.min(MAX_RESERVE));
+                    for _ in 0..element.count {
+                        for property in &element.properties {
+                            match property.kind {
+                                Kind::List { count, item }
+                                    if property.name == "vertex_indices"
+                                        || property.name == "vertex_index" =>
+                                {
+                                    let size = body.index(count)?;
+                                    for _ in 0..size {
+                                        corners.push(body.index(item)?);
+                                    }
+                                    sizes.push(size);
+                                }
+                                kind => body.skip(kind)?,
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    for _ in 0..element.count {
+                        for property in &element.properties {
+                            body.skip(property.kind)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        let vertices = buffers.positions.len() as u32;
+        if corners.iter().any(|&corner| corner >= vertices) {
+            return Err(invalid_data("vertex index out of range"));
+        }
+
+        let face = |indices: [u32; 3]| MeshFace {
+            positions: indices,
+            normals: has_normals.then_some(indices),
+            uvs: has_uvs.then_some(indices),
+            colors: has_colors.then_some(indices),
+            material: 0,
+        };
+        let mut faces = Vec::with_capacity(sizes.len());
+        let mut start = 0;
+        for size in sizes {
+            let polygon = &corners[start..start + size as usize];
+            start += size as usize;
+
+            match polygon {
+                [_, _] | [_] | [] => {}
+                &[a, b, c] => faces.push(face([a, b, c])),
+                _ => {
+                    let points: Vec<_> = polygon
+                        .iter()
+                        .map(|&i| buffers.positions[i as usize])
+                        .collect();
+                    for [a, b, c] in triangulate(&points) {
+                        faces.push(face([polygon[a], polygon[b], polygon[c]]));
+                    }
+                }
+            }
+        }
+
+        Ok(TriangleMesh::new(
+            Arc::new(buffers),
+            faces,
+            vec![self.material.clone()],
+        ))
+    }
+
+    fn push_vertex(
+        &self,
+        buffers: &mut VertexBuffers,
+        values: &[f64; Slot::NAMES.len()],
+        has_normals: bool,
+        has_uvs: bool,
+        has_colors: bool,
+    ) {
+        let value = |slot: Slot| values[slot as usize];
+
+        buffers
+            .positions
+            .push(Point3::new(value(Slot::X), value(Slot::Y), value(Slot::Z)));
+        if has_normals {
+            buffers.normals.push(Vector3::new(
+                value(Slot::Nx),
+                value(Slot::Ny),
+                value(Slot::Nz),
+            ));
+        }
+        if has_uvs {
+            buffers
+                .uvs
+                .push(Vector2::new(value(Slot::U), value(Slot::V)));
+        }
+        if has_colors {
+            let srgb = Color::new(
+                srgb_decode(value(Slot::Red)),
+                srgb_decode(value(Slot::Green)),
+                srgb_decode(value(Slot::Blue)),
+            );
+            buffers.colors.push(srgb);
+        }
+    }
+}
+
+/// Reads the header up to `end_header`, returning the format, the elements and the number of
+/// lines read.
+fn header<R: BufRead>(reader: &mut R) -> io::Result<(Format, Vec<Element>, usize)> {
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+
+    let mut line = String::new();
+    let mut number = 0;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(parse_error(number, "header without end_header"));
+        }
+        number += 1;
+        let error = |message: &str| parse_error(number, message);
+
+        let mut tokens = line.split_whitespace();
+        let keyword = tokens.next().unwrap_or_default();
+        if number == 1 {
+            if keyword != "ply" {
+                return Err(error("not a PLY file"));
+            }
+            continue;
+        }
+
+        match keyword {
+            "format" => {
+                format = Some(match tokens.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    _ => return Err(error("unknown format")),
+                });
+            }
+            "element" => {
+                let (Some(name), Some(count)) = (tokens.next(), tokens.next()) else {
+                    return Err(error("element without name or count"));
+                };
+                elements.push(Element {
+                    name: name.to_owned(),
+                    count: count.parse().map_err(|_| error("invalid element count"))?,
+                    properties: vec![],
+                });
+            }
+            "property" => {
+                let element = elements
+                    .last_mut()
+                    .ok_or_else(|| error("property outside of an element"))?;
+                let scalar = |token: Option<&str>| {
+                    token
+                        .and_then(Scalar::parse)
+                        .ok_or_else(|| error("unknown property type"))
+                };
+                let kind = match tokens.next() {
+                    Some("list") => Kind::List {
+                        count: scalar(tokens.next())?,
+                        item: scalar(tokens.next())?,
+                    },
+                    token => Kind::Scalar(scalar(token)?),
+                };
+                let name = tokens
+                    .next()
+                    .ok_or_else(|| error("property without name"))?;
+
+                element.properties.push(Property {
+                    name: name.to_owned(),
+                    kind,
+                });
+            }
+            "end_header" => break,
+            // Comments and object information.
+            _ => {}
+        }
+    }
+
+    let format = format.ok_or_else(|| parse_error(number, "header without format"))?;
+    Ok((format, elements, number))
+}
+
+impl<R: BufRead> Body<R> {
+    /// Next value, converted to `f64`, which represents all integer types exactly.
+    fn value(&mut self, scalar: Scalar) -> io::Result<f64> {
+        let big_endian = match self.format {
+            Format::Ascii => return self.token(),
+            Format::BinaryLittleEndian => false,
+            Format::BinaryBigEndian => true,
+        };
+
+        let mut bytes = [0; 8];
+        let bytes = &mut bytes[..scalar.size()];
+        self.reader.read_exact(bytes)?;
+        if big_endian {
+            bytes.reverse();
+        }
+
+        Ok(match scalar {
+            Scalar::I8 => i8::from_le_bytes([bytes[0]]) as f64,
+            Scalar::U8 => bytes[0] as f64,
+            Scalar::I16 => i16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::U16 => u16::from_le_bytes([bytes[0], bytes[1]]) as f64,
+            Scalar::I32 => i32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::U32 => u32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F32 => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+            Scalar::F64 => f64::from_le_bytes(bytes.try_into().unwrap()),
+        })
+    }
+
+    /// Next value as a vertex index or list length.
+    fn index(&mut self, scalar: Scalar) -> io::Result<u32> {
+        let value = self.value(scalar)?;
+        if !(0. ..=u32::MAX as f64).contains(&value) || value.fract() != 0. {
+            return Err(self.error("invalid index"));
+        }
+
+        Ok(value as u32)
+    }
+
+    fn skip(&mut self, kind: Kind) -> io::Result<()> {
+        match kind {
+            Kind::Scalar(scalar) => {
+                self.value(scalar)?;
+            }
+            Kind::List { count, item } => {
+                for _ in 0..self.index(count)? {
+                    self.value(item)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Next whitespace-separated number of an ASCII body.
+    fn token(&mut self) -> io::Result<f64> {
+        loop {
+            let rest = self.line[self.offset..].trim_start();
+            if !rest.is_empty() {
+                let start = self.line.len() - rest.len();
+                let end = rest
+                    .find(char::is_whitespace)
+                    .map_or(self.line.len(), |len| start + len);
+                self.offset = end;
+
+                return self.line[start..end]
+                    .parse()
+                    .map_err(|_| self.error("invalid number"));
+            }
+
+            self.line.clear();
+            self.offset = 0;
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.line_number += 1;
+        }
+    }
+
+    fn error(&self, message: &str) -> io::Error {
+        match self.format {
+            Format::Ascii => parse_error(self.line_number, message),
+            _ => invalid_data(message),
+        }
+    }
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// Divisor bringing a value of `slot` into [0, 1]: colors are stored as the full range of
+    /// unsigned integer types, or as floating point fractions.
+    fn color_scale(self, slot: Slot) -> f64 {
+        match (slot, self) {
+            (Slot::Red | Slot::Green | Slot::Blue, Self::U8) => u8::MAX as f64,
+            (Slot::Red | Slot::Green | Slot::Blue, Self::U16) => u16::MAX as f64,
+            (Slot::Red | Slot::Green | Slot::Blue, Self::U32) => u32::MAX as f64,
+            _ => 1.,
+        }
+    }
+}
+
+impl Slot {
+    const NAMES: [(&'static str, Self); 11] = [
+        ("x", Self::X),
+        ("y", Self::Y),
+        ("z", Self::Z),
+        ("nx", Self::Nx),
+        ("ny", Self::Ny),
+        ("nz", Self::Nz),
+        ("u", Self::U),
+        ("v", Self::V),
+        ("red", Self::Red),
+        ("green", Self::Green),
+        ("blue", Self::Blue),
+    ];
+
+    fn of(name: &str) -> Option<Self> {
+        match name {
+            "s" | "texture_u" => Some(Self::U),
+            "t" | "texture_v" => Some(Self::V),
+            name => Self::NAMES
+                .iter()
+                .find(|(slot_name, _)| *slot_name == name)
+                .map(|&(_, slot)| slot),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Hittable, ray::Ray};
+    use cgmath::prelude::*;
+
+    /// L-shaped hexagon, concave at (1, 1), as a single face listed clockwise.
+    const L: [[f32; 2]; 6] = [[0., 0.], [2., 0.], [2., 1.], [1., 1.], [1., 2.], [0., 2.]];
+
+    fn loader() -> PlyLoader {
+        PlyLoader::new(Material::lambertian(Color::new(0.5, 0.5, 0.5).into()))
+    }
+
+    fn hits(mesh: &TriangleMesh, x: f64, y: f64) -> bool {
+        let ray = Ray {
+            origin: Point3::new(x, y, 1.),
+            direction: Vector3::new(0., 0., -1.),
+            time: 0.,
+        };
+        mesh.hit(&ray, 0.001..f64::INFINITY).is_some()
+    }
+
+    fn assert_covers_l(mesh: &TriangleMesh) {
+        assert_eq!(mesh.len(), 4);
+        assert!(hits(mesh, 1.5, 0.5));
+        assert!(hits(mesh, 0.5, 1.5));
+        assert!(!hits(mesh, 1.5, 1.5));
+    }
+
+    #[test]
+    fn reads_ascii_polygon() {
+        let mut ply = String::from(
+            "ply\nformat ascii 1.0\nelement vertex 6\nproperty float x\nproperty float y\n\
+             property float z\nelement face 1\nproperty list uchar int vertex_indices\nend_header\n",
+        );
+        for [x, y] in L {
+            ply += &format!("{} {} 0\n", x, y);
+        }
+        ply += "6 5 4 3 2 1 0\n";
+
+        assert_covers_l(&loader().read(ply.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn reads_binary_polygon_in_both_byte_orders() {
+        for (name, big_endian) in [("binary_little_endian", false), ("binary_big_endian", true)] {
+            let mut ply = format!(
+                "ply\nformat {} 1.0\nelement vertex 6\nproperty float x\nproperty float y\n\
+                 property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+                 end_header\n",
+                name
+            )
+            .into_bytes();
+            let float = |value: f32| {
+                if big_endian {
+                    value.to_be_bytes()
+                } else {
+                    value.to_le_bytes()
+                }
+            };
+            for [x, y] in L {
+                ply.extend(float(x).iter().chain(&float(y)).chain(&float(0.)));
+            }
+            ply.push(6);
+            for index in (0..6i32).rev() {
+                ply.extend(if big_endian {
+                    index.to_be_bytes()
+                } else {
+                    index.to_le_bytes()
+                });
+            }
+
+            assert_covers_l(&loader().read(ply.as_slice()).unwrap());
+        }
+    }
+
+    #[test]
+    fn interpolates_vertex_colors() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                   property float z\nproperty uchar red\nproperty uchar green\nproperty uchar blue\n\
+                   element face 1\nproperty list uchar int vertex_indices\nend_header\n\
+                   0 0 0 255 0 0\n1 0 0 255 0 0\n0 1 0 255 0 0\n3 0 1 2\n";
+        let mesh = loader().read(ply.as_bytes()).unwrap();
+
+        let ray = Ray {
+            origin: Point3::new(0.25, 0.25, 1.),
+            direction: Vector3::new(0., 0., -1.),
+            time: 0.,
+        };
+        let color = mesh
+            .hit(&ray, 0.001..f64::INFINITY)
+            .unwrap()
+            .vertex_color
+            .unwrap();
+        assert!((color - Color::unit_x()).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn rejects_out_of_range_index() {
+        let ply = "ply\nformat ascii 1.0\nelement vertex 3\nproperty float x\nproperty float y\n\
+                   property float z\nelement face 1\nproperty list uchar int vertex_indices\n\
+                   end_header\n0 0 0\n1 0 0\n0 1 0\n3 0 1 3\n";
+
+        assert!(loader().read(ply.as_bytes()).is_err());
+    }
+}
@@ -1,6 +1,12 @@
-use crate::{hittable::HitPayload, ray::Ray, reflect, reflectance, refract, Color};
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    ray::Ray,
+    reflect, reflectance, refract, Color,
+};
 use cgmath::prelude::*;
 use rand::prelude::*;
+use std::io;
 
 #[derive(Clone)]
 pub struct DielectricMaterial {
@@ -21,7 +27,7 @@ impl DielectricMaterial {
         };
 
         let unit_direction = r_in.direction.normalize();
-        let cos_theta = (-unit_direction.dot(payload.normal)).min(1.);
+        let cos_theta = (-unit_direction.dot(payload.shading_normal)).min(1.);
         let sin_theta = (1. - cos_theta * cos_theta).sqrt();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.;
@@ -29,16 +35,30 @@ impl DielectricMaterial {
         let mut rng = rand::thread_rng();
         let scatter_direction =
             if cannot_refract || reflectance(cos_theta, refraction_ratio) > rng.gen() {
-                reflect(unit_direction, payload.normal)
+                payload.keep_side(reflect(unit_direction, payload.shading_normal), true)
             } else {
-                refract(unit_direction, payload.normal, refraction_ratio)
+                let refracted = refract(unit_direction, payload.shading_normal, refraction_ratio);
+                payload.keep_side(refracted, false)
             };
 
         let scattered = Ray {
             origin: payload.point,
             direction: scatter_direction,
+            time: r_in.time,
         };
 
-        return Some((attenuation, scattered));
+        Some((attenuation, scattered))
+    }
+}
+
+impl Encode for DielectricMaterial {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.ir);
+    }
+}
+
+impl Decode for DielectricMaterial {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self { ir: decoder.get()? })
     }
 }
@@ -1,5 +1,10 @@
-use crate::{hittable::HitPayload, texture::Texture, Color};
-use std::sync::Arc;
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    texture::Texture,
+    Color,
+};
+use std::{io, sync::Arc};
 
 #[derive(Clone)]
 pub struct DiffuseLightMaterial {
@@ -13,6 +18,20 @@ impl DiffuseLightMaterial {
     }
 
     pub(crate) fn emitted(&self, payload: &HitPayload) -> Color {
-        self.emit.value(payload.u, payload.v, &payload.point)
+        self.emit.value_at(payload)
+    }
+}
+
+impl Encode for DiffuseLightMaterial {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.emit);
+    }
+}
+
+impl Decode for DiffuseLightMaterial {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            emit: decoder.get()?,
+        })
     }
 }
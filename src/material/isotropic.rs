@@ -1,5 +1,12 @@
-use crate::{hittable::HitPayload, random_unit_vector, ray::Ray, texture::Texture, Color};
-use std::sync::Arc;
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    random_unit_vector,
+    ray::Ray,
+    texture::Texture,
+    Color,
+};
+use std::{io, sync::Arc};
 
 #[derive(Clone)]
 pub struct IsotropicMaterial {
@@ -12,13 +19,32 @@ impl IsotropicMaterial {
         Self { albedo }
     }
 
-    pub(crate) fn scatter(&self, payload: &HitPayload) -> Option<(Color, Ray)> {
+    pub(crate) fn albedo(&self, payload: &HitPayload) -> Color {
+        self.albedo.value_at(payload)
+    }
+
+    pub(crate) fn scatter(&self, r_in: &Ray, payload: &HitPayload) -> Option<(Color, Ray)> {
         let scattered = Ray {
             origin: payload.point,
             direction: random_unit_vector(),
+            time: r_in.time,
         };
-        let attenuation = self.albedo.value(payload.u, payload.v, &payload.point);
+        let attenuation = self.albedo(payload);
 
         Some((attenuation, scattered))
     }
 }
+
+impl Encode for IsotropicMaterial {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.albedo);
+    }
+}
+
+impl Decode for IsotropicMaterial {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            albedo: decoder.get()?,
+        })
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
-    hittable::HitPayload, near_zero, random_unit_vector, ray::Ray, texture::Texture, Color,
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    near_zero, random_unit_vector,
+    ray::Ray,
+    texture::Texture,
+    Color,
 };
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 #[derive(Clone)]
 pub struct LambertianMaterial {
@@ -14,20 +19,36 @@ impl LambertianMaterial {
         Self { albedo }
     }
 
-    pub(crate) fn scatter(&self, payload: &HitPayload) -> Option<(Color, Ray)> {
-        let mut scatter_direction = payload.normal + random_unit_vector();
+    pub(crate) fn albedo(&self, payload: &HitPayload) -> Color {
+        self.albedo.value_at(payload)
+    }
+
+    pub(crate) fn scatter(&self, r_in: &Ray, payload: &HitPayload) -> Option<(Color, Ray)> {
+        let mut scatter_direction = payload.shading_normal + random_unit_vector();
         if near_zero(&scatter_direction) {
-            scatter_direction = payload.normal;
+            scatter_direction = payload.shading_normal;
         }
 
         let scattered = Ray {
             origin: payload.point,
-            direction: scatter_direction,
+            direction: payload.keep_side(scatter_direction, true),
+            time: r_in.time,
         };
 
-        Some((
-            self.albedo.value(payload.u, payload.v, &payload.point),
-            scattered,
-        ))
+        Some((self.albedo(payload), scattered))
+    }
+}
+
+impl Encode for LambertianMaterial {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.albedo);
+    }
+}
+
+impl Decode for LambertianMaterial {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            albedo: decoder.get()?,
+        })
     }
 }
@@ -1,5 +1,12 @@
-use crate::{hittable::HitPayload, random_unit_vector, ray::Ray, reflect, Color};
+use crate::{
+    distributed::wire::{Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    random_unit_vector,
+    ray::Ray,
+    reflect, Color,
+};
 use cgmath::prelude::*;
+use std::io;
 
 #[derive(Clone)]
 pub struct MetalMaterial {
@@ -12,8 +19,13 @@ impl MetalMaterial {
         MetalMaterial { albedo, fuzz }
     }
 
+    #[inline]
+    pub(crate) fn albedo(&self) -> Color {
+        self.albedo
+    }
+
     pub(crate) fn scatter(&self, r_in: &Ray, payload: &HitPayload) -> Option<(Color, Ray)> {
-        let reflected = reflect(r_in.direction.normalize(), payload.normal);
+        let reflected = reflect(r_in.direction.normalize(), payload.shading_normal);
         let scattered_direction = reflected + self.fuzz * random_unit_vector();
 
         if scattered_direction.dot(payload.normal) <= 0. {
@@ -23,8 +35,24 @@ impl MetalMaterial {
         let scattered = Ray {
             origin: payload.point,
             direction: scattered_direction,
+            time: r_in.time,
         };
 
-        Some((self.albedo, scattered))
+        Some((self.albedo(), scattered))
+    }
+}
+
+impl Encode for MetalMaterial {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(&self.albedo).put(&self.fuzz);
+    }
+}
+
+impl Decode for MetalMaterial {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            albedo: decoder.get()?,
+            fuzz: decoder.get()?,
+        })
     }
 }
@@ -1,4 +1,10 @@
-use crate::{hittable::HitPayload, ray::Ray, texture::Texture, Color};
+use crate::{
+    distributed::wire::{unknown_tag, Decode, Decoder, Encode, Encoder},
+    hittable::HitPayload,
+    ray::Ray,
+    texture::Texture,
+    Color,
+};
 
 mod dielectric;
 mod diffuse_light;
@@ -11,7 +17,7 @@ use diffuse_light::DiffuseLightMaterial;
 use isotropic::IsotropicMaterial;
 use lambertian::LambertianMaterial;
 use metal::MetalMaterial;
-use std::sync::Arc;
+use std::{io, sync::Arc};
 
 #[derive(Clone)]
 pub enum Material {
@@ -24,22 +30,33 @@ pub enum Material {
 
 impl Material {
     pub(crate) fn scatter(&self, r_in: &Ray, payload: &HitPayload) -> Option<(Color, Ray)> {
-        match &self {
-            &Self::Lambertian(material) => material.scatter(payload),
-            &Self::Metal(material) => material.scatter(r_in, payload),
-            &Self::Dielectric(material) => material.scatter(r_in, payload),
-            &Self::Isotropic(material) => material.scatter(payload),
-            &Self::DiffuseLight(_) => None,
+        match self {
+            Self::Lambertian(material) => material.scatter(r_in, payload),
+            Self::Metal(material) => material.scatter(r_in, payload),
+            Self::Dielectric(material) => material.scatter(r_in, payload),
+            Self::Isotropic(material) => material.scatter(r_in, payload),
+            Self::DiffuseLight(_) => None,
         }
     }
 
     pub(crate) fn emitted(&self, payload: &HitPayload) -> Color {
-        match &self {
-            &Self::DiffuseLight(material) => material.emitted(payload),
+        match self {
+            Self::DiffuseLight(material) => material.emitted(payload),
             _ => Color::from([0.; 3]),
         }
     }
 
+    /// Surface color at the hit, written as the albedo AOV.
+    pub(crate) fn albedo(&self, payload: &HitPayload) -> Color {
+        match self {
+            Self::Lambertian(material) => material.albedo(payload),
+            Self::Metal(material) => material.albedo(),
+            Self::Dielectric(_) => Color::from([1.; 3]),
+            Self::Isotropic(material) => material.albedo(payload),
+            Self::DiffuseLight(material) => material.emitted(payload),
+        }
+    }
+
     #[allow(private_bounds)]
     pub fn lambertian<T: Texture + Send + Sync + 'static>(albedo: Arc<T>) -> Self {
         Self::Lambertian(LambertianMaterial::new(albedo))
@@ -63,3 +80,28 @@ impl Material {
         Self::DiffuseLight(DiffuseLightMaterial::new(emit))
     }
 }
+
+impl Encode for Material {
+    fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Self::Lambertian(material) => encoder.put(&0u8).put(material),
+            Self::Metal(material) => encoder.put(&1u8).put(material),
+            Self::Dielectric(material) => encoder.put(&2u8).put(material),
+            Self::Isotropic(material) => encoder.put(&3u8).put(material),
+            Self::DiffuseLight(material) => encoder.put(&4u8).put(material),
+        };
+    }
+}
+
+impl Decode for Material {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(match decoder.get()? {
+            0 => Self::Lambertian(decoder.get()?),
+            1 => Self::Metal(decoder.get()?),
+            2 => Self::Dielectric(decoder.get()?),
+            3 => Self::Isotropic(decoder.get()?),
+            4 => Self::DiffuseLight(decoder.get()?),
+            tag => return Err(unknown_tag("material", tag)),
+        })
+    }
+}
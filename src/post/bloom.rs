@@ -0,0 +1,42 @@
+use super::{gaussian_blur, FrameBuffer};
+use crate::Color;
+
+/// Glow around highlights: the parts of the image brighter than `threshold` are blurred and added
+/// back.
+#[derive(Clone)]
+pub struct Bloom {
+    threshold: f64,
+    intensity: f64,
+    radius: f64,
+}
+
+impl Bloom {
+    /// `radius` is the standard deviation of the narrowest of the blurs, in pixels.
+    pub fn new(threshold: f64, intensity: f64, radius: f64) -> Self {
+        Self {
+            threshold,
+            intensity,
+            radius,
+        }
+    }
+
+    pub(crate) fn apply(&self, buffer: &mut FrameBuffer) {
+        const OCTAVES: i32 = 3;
+
+        let bright = buffer.bright_pass(self.threshold);
+
+        // Mixing blurs of doubling radii gives the long tail of a real lens glow.
+        let mut glow = vec![Color::new(0., 0., 0.); bright.len()];
+        for octave in 0..OCTAVES {
+            let sigma = self.radius * 2f64.powi(octave);
+            let blurred = gaussian_blur(&bright, buffer.width, buffer.height, sigma);
+            for (glow, blurred) in glow.iter_mut().zip(blurred) {
+                *glow += blurred / OCTAVES as f64;
+            }
+        }
+
+        for (pixel, glow) in buffer.pixels.iter_mut().zip(glow) {
+            *pixel += self.intensity * glow;
+        }
+    }
+}
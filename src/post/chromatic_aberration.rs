@@ -0,0 +1,42 @@
+use super::FrameBuffer;
+use crate::Color;
+
+/// Lateral chromatic aberration: red and blue are magnified slightly differently from green,
+/// fringing edges towards the corners of the frame.
+#[derive(Clone)]
+pub struct ChromaticAberration {
+    strength: f64,
+}
+
+impl ChromaticAberration {
+    /// `strength` is the relative magnification of red over green, and of green over blue.
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+
+    pub(crate) fn apply(&self, buffer: &mut FrameBuffer) {
+        let center = 0.5 * buffer.full_size - buffer.offset;
+
+        let pixels = (0..buffer.pixels.len())
+            .map(|idx| {
+                let x = (idx as u32 % buffer.width) as f64 + 0.5;
+                let y = (idx as u32 / buffer.width) as f64 + 0.5;
+
+                // Red is magnified, so the red seen at a pixel comes from closer to the center.
+                let channel = |scale: f64| {
+                    let x = center.x + (x - center.x) / scale;
+                    let y = center.y + (y - center.y) / scale;
+                    buffer.sample(x, y)
+                };
+
+                Color::new(
+                    channel(1. + self.strength).x,
+                    buffer.pixels[idx].y,
+                    channel(1. - self.strength).z,
+                )
+            })
+            .collect();
+
+        buffer.pixels = pixels;
+    }
+}
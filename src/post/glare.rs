@@ -0,0 +1,78 @@
+use super::FrameBuffer;
+use crate::Color;
+use cgmath::prelude::*;
+use rayon::prelude::*;
+use std::f64::consts::PI;
+
+/// Star-shaped streaks radiating from highlights, as produced by the blades of an aperture.
+#[derive(Clone)]
+pub struct Glare {
+    threshold: f64,
+    intensity: f64,
+    streaks: u32,
+    length: f64,
+    rotation: f64,
+}
+
+impl Glare {
+    /// `streaks` evenly spaced streaks fade out over about `length` pixels.
+    pub fn new(threshold: f64, intensity: f64, streaks: u32, length: f64) -> Self {
+        Self {
+            threshold,
+            intensity,
+            streaks,
+            length,
+            rotation: 0.,
+        }
+    }
+
+    /// Rotates the streaks by `degrees`.
+    pub fn rotated(mut self, degrees: f64) -> Self {
+        self.rotation = degrees;
+        self
+    }
+
+    pub(crate) fn apply(&self, buffer: &mut FrameBuffer) {
+        if self.streaks == 0 || self.length.is_nan() || self.length <= 0. {
+            return;
+        }
+
+        let bright = FrameBuffer {
+            pixels: buffer.bright_pass(self.threshold),
+            ..*buffer
+        };
+
+        let steps = (3. * self.length).ceil() as u32;
+        let falloff: Vec<f64> = (1..=steps)
+            .map(|k| (-(k as f64) / self.length).exp())
+            .collect();
+        let total = self.streaks as f64 * falloff.iter().sum::<f64>();
+
+        let directions: Vec<_> = (0..self.streaks)
+            .map(|s| {
+                let angle = self.rotation.to_radians() + 2. * PI * s as f64 / self.streaks as f64;
+                (angle.cos(), angle.sin())
+            })
+            .collect();
+
+        let width = buffer.width;
+        buffer
+            .pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let x = (idx as u32 % width) as f64 + 0.5;
+                let y = (idx as u32 / width) as f64 + 0.5;
+
+                let mut streak = Color::zero();
+                for &(dx, dy) in &directions {
+                    for (k, weight) in falloff.iter().enumerate() {
+                        let d = (k + 1) as f64;
+                        streak += *weight * bright.sample(x - d * dx, y - d * dy);
+                    }
+                }
+
+                *pixel += self.intensity * streak / total;
+            });
+    }
+}
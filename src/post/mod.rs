@@ -0,0 +1,200 @@
+use crate::Color;
+use cgmath::{prelude::*, Vector2};
+
+mod bloom;
+mod chromatic_aberration;
+mod glare;
+mod vignette;
+
+pub use bloom::Bloom;
+pub use chromatic_aberration::ChromaticAberration;
+pub use glare::Glare;
+pub use vignette::Vignette;
+
+/// Image-space effect applied to the scene-linear framebuffer before tone mapping.
+///
+/// Effects are applied in the order they are given, e.g. bloom before vignetting darkens the
+/// bloom with the rest of the image.
+#[derive(Clone)]
+pub enum PostEffect {
+    Bloom(Bloom),
+    Glare(Glare),
+    Vignette(Vignette),
+    ChromaticAberration(ChromaticAberration),
+}
+
+impl PostEffect {
+    pub(crate) fn apply(&self, buffer: &mut FrameBuffer) {
+        // Nothing to do for an empty crop, which has no edge pixels to clamp to either.
+        if buffer.pixels.is_empty() {
+            return;
+        }
+
+        match self {
+            Self::Bloom(effect) => effect.apply(buffer),
+            Self::Glare(effect) => effect.apply(buffer),
+            Self::Vignette(effect) => effect.apply(buffer),
+            Self::ChromaticAberration(effect) => effect.apply(buffer),
+        }
+    }
+
+    pub fn bloom(threshold: f64, intensity: f64, radius: f64) -> Self {
+        Self::Bloom(Bloom::new(threshold, intensity, radius))
+    }
+
+    pub fn glare(threshold: f64, intensity: f64, streaks: u32, length: f64) -> Self {
+        Self::Glare(Glare::new(threshold, intensity, streaks, length))
+    }
+
+    pub fn vignette(strength: f64) -> Self {
+        Self::Vignette(Vignette::new(strength))
+    }
+
+    pub fn chromatic_aberration(strength: f64) -> Self {
+        Self::ChromaticAberration(ChromaticAberration::new(strength))
+    }
+}
+
+/// Region of the scene-linear image being post-processed.
+pub(crate) struct FrameBuffer {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) pixels: Vec<Color>,
+
+    /// Position of the region in the full image, so that effects depending on the position in the
+    /// frame agree between crops.
+    pub(crate) offset: Vector2<f64>,
+    pub(crate) full_size: Vector2<f64>,
+}
+
+impl FrameBuffer {
+    /// Pixel `(x, y)` of the region, clamped to its edges.
+    fn get(&self, x: i64, y: i64) -> Color {
+        let x = x.clamp(0, self.width as i64 - 1) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Bilinearly interpolated color at the continuous position `(x, y)` of the region, where pixel
+    /// centers lie at half-integer positions.
+    fn sample(&self, x: f64, y: f64) -> Color {
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (s, t) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.get(x0, y0) * (1. - s) + self.get(x0 + 1, y0) * s;
+        let bottom = self.get(x0, y0 + 1) * (1. - s) + self.get(x0 + 1, y0 + 1) * s;
+        top * (1. - t) + bottom * t
+    }
+
+    /// Position of the center of pixel `idx` relative to the center of the full image, normalized
+    /// so that the corners lie at distance 1.
+    fn centered(&self, idx: usize) -> Vector2<f64> {
+        let x = (idx as u32 % self.width) as f64 + 0.5;
+        let y = (idx as u32 / self.width) as f64 + 0.5;
+        let p = self.offset + Vector2::new(x, y) - 0.5 * self.full_size;
+
+        p / (0.5 * self.full_size.magnitude())
+    }
+
+    /// Parts of the pixels brighter than `threshold`.
+    fn bright_pass(&self, threshold: f64) -> Vec<Color> {
+        self.pixels
+            .iter()
+            .map(|&color| color.map(|c| (c - threshold).max(0.)))
+            .collect()
+    }
+}
+
+/// Blurs `pixels`, a `width` x `height` image, with a Gaussian of standard deviation `sigma`, or
+/// copies them unchanged if `sigma` is not positive.
+fn gaussian_blur(pixels: &[Color], width: u32, height: u32, sigma: f64) -> Vec<Color> {
+    if sigma.is_nan() || sigma <= 0. {
+        return pixels.to_vec();
+    }
+
+    let radius = (3. * sigma).ceil() as i64;
+    let weights: Vec<f64> = (-radius..=radius)
+        .map(|k| (-((k * k) as f64) / (2. * sigma * sigma)).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+
+    let (w, h) = (width as i64, height as i64);
+    let convolve = |pixels: &[Color], step: (i64, i64)| -> Vec<Color> {
+        (0..pixels.len() as i64)
+            .map(|idx| {
+                let (x, y) = (idx % w, idx / w);
+                (-radius..=radius)
+                    .zip(&weights)
+                    .map(|(k, weight)| {
+                        let xk = (x + k * step.0).clamp(0, w - 1);
+                        let yk = (y + k * step.1).clamp(0, h - 1);
+                        pixels[(yk * w + xk) as usize] * *weight
+                    })
+                    .sum::<Color>()
+                    / total
+            })
+            .collect()
+    };
+
+    let horizontal = convolve(pixels, (1, 0));
+    convolve(&horizontal, (0, 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(width: u32, height: u32, pixels: Vec<Color>) -> FrameBuffer {
+        FrameBuffer {
+            width,
+            height,
+            pixels,
+            offset: Vector2::zero(),
+            full_size: Vector2::new(width as f64, height as f64),
+        }
+    }
+
+    #[test]
+    fn blur_without_sigma_copies_pixels() {
+        let pixels: Vec<_> = (0..6).map(|i| Color::new(i as f64, 0., 1.)).collect();
+        for sigma in [0., -1., f64::NAN] {
+            assert_eq!(gaussian_blur(&pixels, 3, 2, sigma), pixels);
+        }
+    }
+
+    #[test]
+    fn glare_without_length_keeps_pixels() {
+        let pixels: Vec<_> = (0..6).map(|i| Color::new(i as f64, 0., 1.)).collect();
+        for length in [0., -1., f64::NAN] {
+            let mut glared = buffer(3, 2, pixels.clone());
+            PostEffect::glare(0.5, 1., 4, length).apply(&mut glared);
+            assert_eq!(glared.pixels, pixels);
+        }
+    }
+
+    #[test]
+    fn blur_keeps_constant_image() {
+        let pixels = vec![Color::new(0.25, 0.5, 1.); 12];
+        for blurred in gaussian_blur(&pixels, 4, 3, 1.5) {
+            assert!((blurred - pixels[0]).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn effects_skip_empty_buffer() {
+        let effects = [
+            PostEffect::bloom(0.5, 1., 0.1),
+            PostEffect::glare(0.5, 1., 4, 0.1),
+            PostEffect::vignette(0.5),
+            PostEffect::chromatic_aberration(0.5),
+        ];
+        for effect in effects {
+            let mut empty = buffer(0, 0, vec![]);
+            effect.apply(&mut empty);
+            assert!(empty.pixels.is_empty());
+        }
+    }
+}
@@ -0,0 +1,23 @@
+use super::FrameBuffer;
+use cgmath::prelude::*;
+
+/// Natural darkening towards the corners of the frame following the cos⁴ law.
+#[derive(Clone)]
+pub struct Vignette {
+    strength: f64,
+}
+
+impl Vignette {
+    /// `strength` is the tangent of the off-axis angle at the corners of the frame: 0 disables the
+    /// vignetting and 1 darkens the corners to a quarter.
+    pub fn new(strength: f64) -> Self {
+        Self { strength }
+    }
+
+    pub(crate) fn apply(&self, buffer: &mut FrameBuffer) {
+        for idx in 0..buffer.pixels.len() {
+            let r = buffer.centered(idx).magnitude2() * self.strength * self.strength;
+            buffer.pixels[idx] /= (1. + r) * (1. + r);
+        }
+    }
+}
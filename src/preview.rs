@@ -0,0 +1,380 @@
+//! Embedded HTTP server to watch a render converge in a browser.
+//!
+//! [`PreviewServer`] is a [`ProgressObserver`] serving
+//! - `/`, a page showing the image and the progress,
+//! - `/image.png`, the tone-mapped film as of the last completed pass,
+//! - `/status.json`, the progress and statistics of the render.
+
+use crate::{
+    film::Film,
+    post::PostEffect,
+    progress::{NoProgress, Pass, ProgressObserver},
+    stats::RenderStats,
+    tonemap::ToneMapping,
+};
+use image::{ImageOutputFormat, RgbImage};
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Cursor, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+const PAGE: &str = include_str!("preview.html");
+
+/// Connections served at the same time, beyond which new ones are closed right away.
+const MAX_CONNECTIONS: usize = 16;
+
+/// Longest request line and headers accepted, which is plenty for a browser asking for a page.
+const MAX_HEADER_BYTES: u64 = 8 * 1024;
+
+#[derive(Clone, Copy, Default)]
+enum Phase {
+    #[default]
+    Idle,
+    Rendering,
+    Finished,
+    Cancelled,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Idle => "idle",
+            Self::Rendering => "rendering",
+            Self::Finished => "finished",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    phase: Phase,
+    pixels: u64,
+    fraction: f64,
+    eta: Option<Duration>,
+    passes: u32,
+    samples: u32,
+    stats: RenderStats,
+    snapshot: Option<Arc<Snapshot>>,
+}
+
+/// Film of the last completed pass, tone-mapped only once a client asks for the image.
+struct Snapshot {
+    film: Film,
+    post_effects: Vec<PostEffect>,
+    tone_mapping: ToneMapping,
+    image: OnceLock<RgbImage>,
+}
+
+impl Snapshot {
+    fn image(&self) -> &RgbImage {
+        self.image
+            .get_or_init(|| self.film.to_image(&self.post_effects, &self.tone_mapping))
+    }
+}
+
+/// Serves the progress of renders over HTTP while forwarding it to another observer.
+///
+/// The server keeps running in a background thread until the process exits, so it can be reused
+/// for several renders and shows the last image after a render is done. It does not authenticate
+/// clients and should only listen on local addresses.
+pub struct PreviewServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    observer: Box<dyn ProgressObserver>,
+}
+
+impl PreviewServer {
+    /// Starts serving on `addr`, e.g. `"127.0.0.1:8000"`.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let shared = state.clone();
+        thread::Builder::new()
+            .name("preview-server".to_owned())
+            .spawn(move || accept(listener, shared))?;
+
+        Ok(Self {
+            addr,
+            state,
+            observer: Box::new(NoProgress),
+        })
+    }
+
+    /// Also reports the progress to `observer`, e.g. to keep the progress bar.
+    pub fn forward<P: ProgressObserver + 'static>(mut self, observer: P) -> Self {
+        self.observer = Box::new(observer);
+        self
+    }
+
+    #[inline]
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl ProgressObserver for PreviewServer {
+    fn started(&self, pixels: u64) {
+        *self.state.lock().unwrap() = State {
+            phase: Phase::Rendering,
+            pixels,
+            ..State::default()
+        };
+        self.observer.started(pixels);
+    }
+
+    fn progress(&self, fraction: f64, eta: Option<Duration>) {
+        let mut state = self.state.lock().unwrap();
+        state.fraction = fraction;
+        state.eta = eta;
+        drop(state);
+
+        self.observer.progress(fraction, eta);
+    }
+
+    fn pass(&self, pass: &Pass<'_>) {
+        // Copying the film is much cheaper than post-processing it, which is left to requests.
+        let snapshot = Arc::new(Snapshot {
+            film: pass.film.clone(),
+            post_effects: pass.post_effects.to_vec(),
+            tone_mapping: *pass.tone_mapping,
+            image: OnceLock::new(),
+        });
+
+        let mut state = self.state.lock().unwrap();
+        state.passes += 1;
+        state.samples = pass.samples;
+        state.stats = pass.stats;
+        state.snapshot = Some(snapshot);
+        drop(state);
+
+        self.observer.pass(pass);
+    }
+
+    fn finished(&self, stats: &RenderStats) {
+        let mut state = self.state.lock().unwrap();
+        if stats.cancelled {
+            state.phase = Phase::Cancelled;
+        } else {
+            state.phase = Phase::Finished;
+            state.fraction = 1.;
+        }
+        state.eta = None;
+        state.stats = *stats;
+        drop(state);
+
+        self.observer.finished(stats);
+    }
+}
+
+fn accept(listener: TcpListener, state: Arc<Mutex<State>>) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(stream) = stream else {
+            continue;
+        };
+        // Dropping the stream closes the connection.
+        if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+            connections.fetch_sub(1, Ordering::Relaxed);
+            continue;
+        }
+
+        let state = state.clone();
+        let connections = connections.clone();
+        thread::spawn(move || {
+            // A client that goes away mid-request is no concern of the render.
+            respond(stream, &state).ok();
+            connections.fetch_sub(1, Ordering::Relaxed);
+        });
+    }
+}
+
+fn respond(mut stream: TcpStream, state: &Mutex<State>) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+
+    let mut reader = BufReader::new((&stream).take(MAX_HEADER_BYTES));
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // The headers are of no interest, but must be read before responding.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim_end().is_empty() {
+        header.clear();
+    }
+    if reader.get_ref().limit() == 0 {
+        return write_response(
+            &mut stream,
+            "431 Request Header Fields Too Large",
+            "text/plain",
+            b"",
+        );
+    }
+
+    let mut parts = request.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let path = target.split('?').next().unwrap_or_default();
+
+    if method != "GET" {
+        return write_response(&mut stream, "405 Method Not Allowed", "text/plain", b"");
+    }
+
+    match path {
+        "/" => write_response(
+            &mut stream,
+            "200 OK",
+            "text/html; charset=utf-8",
+            PAGE.as_bytes(),
+        ),
+        "/status.json" => {
+            let status = status_json(&state.lock().unwrap());
+            write_response(&mut stream, "200 OK", "application/json", status.as_bytes())
+        }
+        "/image.png" => {
+            let Some(snapshot) = state.lock().unwrap().snapshot.clone() else {
+                return write_response(
+                    &mut stream,
+                    "503 Service Unavailable",
+                    "text/plain",
+                    b"no pass completed yet\n",
+                );
+            };
+
+            let mut png = Vec::new();
+            match snapshot
+                .image()
+                .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+            {
+                Ok(()) => write_response(&mut stream, "200 OK", "image/png", &png),
+                Err(err) => write_response(
+                    &mut stream,
+                    "500 Internal Server Error",
+                    "text/plain",
+                    err.to_string().as_bytes(),
+                ),
+            }
+        }
+        _ => write_response(&mut stream, "404 Not Found", "text/plain", b"not found\n"),
+    }
+}
+
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n",
+        status,
+        content_type,
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+/// JSON has no representation of infinities and NaN, e.g. the ETA before it is known.
+fn number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_owned()
+    }
+}
+
+fn status_json(state: &State) -> String {
+    let stats = &state.stats;
+    let eta = state.eta.map_or(f64::NAN, |eta| eta.as_secs_f64());
+
+    let mut json = String::new();
+    write!(
+        json,
+        r#"{{"state":"{}","progress":{},"eta":{},"pixels":{},"passes":{},"samples":{},"#,
+        state.phase.name(),
+        number(state.fraction),
+        number(eta),
+        state.pixels,
+        state.passes,
+        state.samples,
+    )
+    .unwrap();
+    write!(
+        json,
+        r#""stats":{{"camera_rays":{},"secondary_rays":{},"average_path_length":{},"rays_per_second":{},"#,
+        stats.camera_rays,
+        stats.secondary_rays,
+        number(stats.average_path_length()),
+        number(stats.rays_per_second()),
+    )
+    .unwrap();
+    write!(
+        json,
+        r#""bvh_nodes_visited":{},"sphere_tests":{},"quad_tests":{},"triangle_tests":{},"shape_tests":{},"medium_tests":{},"bvh_build":{},"render":{},"write":{}}}}}"#,
+        stats.bvh_nodes_visited,
+        stats.sphere_tests,
+        stats.quad_tests,
+        stats.triangle_tests,
+        stats.shape_tests,
+        stats.medium_tests,
+        number(stats.bvh_build.as_secs_f64()),
+        number(stats.render.as_secs_f64()),
+        number(stats.write.as_secs_f64()),
+    )
+    .unwrap();
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::film::CropWindow;
+
+    fn get(server: &PreviewServer, request: &str) -> String {
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn tone_maps_image_when_requested() {
+        let server = PreviewServer::bind("127.0.0.1:0").unwrap();
+        let film = Film::new(CropWindow::new(0, 0, 4, 3), 4, 3, Default::default());
+        server.started(12);
+        server.pass(&Pass {
+            film: &film,
+            samples: 1,
+            post_effects: &[],
+            tone_mapping: &ToneMapping::default(),
+            stats: RenderStats::default(),
+        });
+
+        let snapshot = server.state.lock().unwrap().snapshot.clone().unwrap();
+        assert!(snapshot.image.get().is_none());
+
+        let response = get(&server, "GET /image.png HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(snapshot.image.get().is_some());
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let server = PreviewServer::bind("127.0.0.1:0").unwrap();
+        // Exactly as much as the server reads, so that it does not reset the connection.
+        let mut request = "GET / HTTP/1.1\r\nX-Padding: ".to_owned();
+        request.push_str(&"a".repeat(MAX_HEADER_BYTES as usize - request.len()));
+        let response = get(&server, &request);
+        assert!(response.starts_with("HTTP/1.1 431"));
+    }
+}
@@ -0,0 +1,167 @@
+use crate::{film::Film, post::PostEffect, stats::RenderStats, tonemap::ToneMapping};
+use indicatif::{ProgressBar, ProgressStyle};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Receives the progress of renders.
+///
+/// `progress` is called from the render threads, at most once per thousandth of the render.
+pub trait ProgressObserver: Send + Sync {
+    /// A render of `pixels` pixels starts.
+    fn started(&self, _pixels: u64) {}
+
+    /// `fraction` of the render is done, and the rest is estimated to take `eta`.
+    fn progress(&self, _fraction: f64, _eta: Option<Duration>) {}
+
+    /// A sample pass over every pixel is complete. Called from the thread that started the render,
+    /// which waits for it before the next pass.
+    fn pass(&self, _pass: &Pass<'_>) {}
+
+    fn finished(&self, _stats: &RenderStats) {}
+}
+
+/// State of a render after a completed sample pass.
+pub struct Pass<'a> {
+    pub film: &'a Film,
+    /// Samples per pixel taken so far.
+    pub samples: u32,
+    /// How the film is going to be saved.
+    pub post_effects: &'a [PostEffect],
+    pub tone_mapping: &'a ToneMapping,
+    /// Work done so far.
+    pub stats: RenderStats,
+}
+
+/// Reports nothing.
+pub struct NoProgress;
+
+impl ProgressObserver for NoProgress {}
+
+/// Draws a progress bar to the terminal and prints the statistics when done.
+#[derive(Default)]
+pub struct IndicatifProgress {
+    bar: Mutex<Option<ProgressBar>>,
+}
+
+impl IndicatifProgress {
+    const STEPS: u64 = 1000;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl ProgressObserver for IndicatifProgress {
+    fn started(&self, _pixels: u64) {
+        let bar = ProgressBar::new(Self::STEPS).with_style(
+            ProgressStyle::with_template(
+                "[{elapsed_precise}] {wide_bar} {percent:>3}% (ETA {eta})",
+            )
+            .unwrap(),
+        );
+        *self.bar.lock().unwrap() = Some(bar);
+    }
+
+    fn progress(&self, fraction: f64, _eta: Option<Duration>) {
+        if let Some(bar) = self.bar.lock().unwrap().as_ref() {
+            bar.set_position((fraction * Self::STEPS as f64) as u64);
+        }
+    }
+
+    fn finished(&self, stats: &RenderStats) {
+        if let Some(bar) = self.bar.lock().unwrap().take() {
+            bar.finish();
+        }
+        println!("{}", stats);
+    }
+}
+
+/// Writes a plain line to stderr at most every `interval`, suitable for logs.
+pub struct LogProgress {
+    interval: Duration,
+    last: Mutex<Instant>,
+}
+
+impl LogProgress {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::new(Instant::now()),
+        }
+    }
+}
+
+impl ProgressObserver for LogProgress {
+    fn started(&self, pixels: u64) {
+        *self.last.lock().unwrap() = Instant::now();
+        eprintln!("render started: {} pixels", pixels);
+    }
+
+    fn progress(&self, fraction: f64, eta: Option<Duration>) {
+        // Never hold up a render thread for a log line.
+        let Ok(mut last) = self.last.try_lock() else {
+            return;
+        };
+        if last.elapsed() < self.interval {
+            return;
+        }
+        *last = Instant::now();
+
+        match eta {
+            Some(eta) => eprintln!("render {:5.1}% done, {:.0?} left", 100. * fraction, eta),
+            None => eprintln!("render {:5.1}% done", 100. * fraction),
+        }
+    }
+
+    fn finished(&self, stats: &RenderStats) {
+        eprintln!("render finished\n{}", stats);
+    }
+}
+
+/// Turns the work done by the render threads into throttled calls of an observer.
+pub(crate) struct Tracker<'a> {
+    observer: &'a dyn ProgressObserver,
+    start: Instant,
+    /// Units of work of the whole render, or 0 if unknown.
+    total: u64,
+    done: AtomicU64,
+    reported: AtomicU64,
+}
+
+impl<'a> Tracker<'a> {
+    const STEPS: f64 = 1000.;
+
+    pub(crate) fn new(observer: &'a dyn ProgressObserver, total: u64) -> Self {
+        Self {
+            observer,
+            start: Instant::now(),
+            total,
+            done: AtomicU64::new(0),
+            reported: AtomicU64::new(0),
+        }
+    }
+
+    /// Records `amount` more units of work done.
+    pub(crate) fn advance(&self, amount: u64) {
+        let done = self.done.fetch_add(amount, Ordering::Relaxed) + amount;
+        if self.total > 0 {
+            self.report(done as f64 / self.total as f64);
+        }
+    }
+
+    pub(crate) fn report(&self, fraction: f64) {
+        let fraction = fraction.clamp(0., 1.);
+        let step = (fraction * Self::STEPS) as u64;
+        if self.reported.fetch_max(step, Ordering::Relaxed) >= step {
+            return;
+        }
+
+        let eta = (fraction > 0.).then(|| self.start.elapsed().mul_f64(fraction.recip() - 1.));
+        self.observer.progress(fraction, eta);
+    }
+}
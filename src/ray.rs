@@ -1,9 +1,16 @@
-use crate::{hittable::Hittable, Color};
+use crate::{
+    colorspace::ColorSpace,
+    film::Features,
+    hittable::Hittable,
+    stats::{self, Counter},
+    Color,
+};
 use cgmath::{prelude::*, Point3, Vector3};
 
 pub(crate) struct Ray {
     pub(crate) origin: Point3<f64>,
     pub(crate) direction: Vector3<f64>,
+    pub(crate) time: f64,
 }
 
 impl Ray {
@@ -11,24 +18,86 @@ impl Ray {
         self.origin + t * self.direction
     }
 
-    pub(crate) fn color<H: Hittable>(&self, world: &H, background: &Color, depth: u32) -> Color {
-        if depth == 0 {
-            return Color::zero();
-        }
+    /// Radiance arriving along the ray through at most `max_depth` hits, together with the
+    /// surface features of the first hit for the AOVs, both in `space`. Materials give their
+    /// colors in linear sRGB; `background` is already in `space`.
+    pub(crate) fn trace<H: Hittable>(
+        self,
+        world: &H,
+        background: &Color,
+        max_depth: u32,
+        clamp: &SampleClamp,
+        space: ColorSpace,
+    ) -> (Color, Option<Features>) {
+        let mut ray = self;
+        let mut color = Color::zero();
+        let mut throughput = Color::from([1.; 3]);
+        let mut features = None;
 
-        if let Some(payload) = world.hit(self, 0.001..f64::INFINITY) {
-            let color_from_emission = payload.material.emitted(&payload);
+        for bounce in 0..max_depth {
+            stats::count(if bounce == 0 {
+                Counter::CameraRays
+            } else {
+                Counter::SecondaryRays
+            });
 
-            if let Some((attenuation, scattered)) = payload.material.scatter(self, &payload) {
-                let color_from_scatter =
-                    attenuation.mul_element_wise(scattered.color(world, background, depth - 1));
+            let Some(payload) = world.hit(&ray, 0.001..f64::INFINITY) else {
+                color += clamp.apply(bounce, throughput.mul_element_wise(*background));
+                break;
+            };
 
-                return color_from_scatter + color_from_emission;
+            if bounce == 0 {
+                features = Some(Features {
+                    albedo: space.from_linear_srgb(payload.material.albedo(&payload)),
+                    normal: payload.shading_normal.normalize(),
+                    depth: payload.t * ray.direction.magnitude(),
+                });
             }
 
-            color_from_emission
-        } else {
-            return *background;
+            let emitted = space.from_linear_srgb(payload.material.emitted(&payload));
+            color += clamp.apply(bounce, throughput.mul_element_wise(emitted));
+
+            let Some((attenuation, scattered)) = payload.material.scatter(&ray, &payload) else {
+                break;
+            };
+            throughput.mul_assign_element_wise(space.from_linear_srgb(attenuation));
+            ray = scattered;
+        }
+
+        (color, features)
+    }
+}
+
+/// Upper bounds on the radiance a single path contributes, trading bias for fewer fireflies.
+///
+/// Light reached after one scattering event is direct lighting, light reached after more is
+/// indirect. Emitters and background seen straight from the camera are never clamped.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct SampleClamp {
+    pub(crate) direct: Option<f64>,
+    pub(crate) indirect: Option<f64>,
+}
+
+impl SampleClamp {
+    /// Scales `color`, the light found after `bounce` scattering events, down to the limit while
+    /// keeping its hue.
+    fn apply(&self, bounce: u32, color: Color) -> Color {
+        let limit = match bounce {
+            0 => None,
+            1 => self.direct,
+            _ => self.indirect,
+        };
+
+        match limit {
+            Some(limit) => {
+                let max = color.x.max(color.y).max(color.z);
+                if max > limit {
+                    color * (limit / max)
+                } else {
+                    color
+                }
+            }
+            None => color,
         }
     }
 }
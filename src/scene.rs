@@ -0,0 +1,324 @@
+//! Editable hierarchy of named objects, flattened into a [`Bvh`] for rendering.
+//!
+//! A [`SceneGraph`] keeps the objects of a scene as [`SceneNode`]s that can be found by name,
+//! moved, restyled, hidden and removed between renders, without rebuilding the scene.
+
+use crate::{
+    hittable::{
+        is_projective, is_singular, linear_part, Bvh, Hittable, HittableList, MaterialOverride,
+        Transform,
+    },
+    material::Material,
+};
+use cgmath::{prelude::*, Matrix4};
+use std::{collections::HashMap, sync::Arc};
+
+/// Handle of a node, valid until the node is removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+pub struct SceneNode {
+    name: String,
+    /// Placement in the frame of the parent, see [`Transform::from_matrix`].
+    pub transform: Matrix4<f64>,
+    /// Material replacing that of the object of the node and its descendants, unless they set
+    /// one of their own.
+    pub material: Option<Material>,
+    /// Hidden nodes are left out of the render together with their descendants.
+    pub visible: bool,
+
+    object: Option<Arc<dyn Hittable + Send + Sync>>,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+/// Tree of [`SceneNode`]s, each placed relative to its parent.
+#[derive(Default)]
+pub struct SceneGraph {
+    /// Nodes by [`NodeId`], with `None` left in the place of removed ones.
+    nodes: Vec<Option<SceneNode>>,
+    roots: Vec<NodeId>,
+    /// Nodes that were not removed by name, in the order they were added.
+    names: HashMap<String, Vec<NodeId>>,
+}
+
+impl SceneNode {
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[allow(private_bounds)]
+    #[inline]
+    pub fn object<H: Hittable + Send + Sync + 'static>(&mut self, object: Arc<H>) -> &mut Self {
+        self.object = Some(object);
+        self
+    }
+
+    /// Makes the node a pure group of its children.
+    #[inline]
+    pub fn remove_object(&mut self) -> &mut Self {
+        self.object = None;
+        self
+    }
+
+    #[inline]
+    pub fn transform(&mut self, transform: Matrix4<f64>) -> &mut Self {
+        self.transform = transform;
+        self
+    }
+
+    #[inline]
+    pub fn material(&mut self, material: Material) -> &mut Self {
+        self.material = Some(material);
+        self
+    }
+
+    #[inline]
+    pub fn visible(&mut self, visible: bool) -> &mut Self {
+        self.visible = visible;
+        self
+    }
+
+    #[inline]
+    pub fn parent(&self) -> Option<NodeId> {
+        self.parent
+    }
+
+    #[inline]
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an empty, visible node named `name` under `parent`, or as a root.
+    ///
+    /// # Panics
+    ///
+    /// If `parent` was removed.
+    pub fn add(&mut self, name: &str, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.siblings(parent).push(id);
+        self.names.entry(name.to_owned()).or_default().push(id);
+        self.nodes.push(Some(SceneNode {
+            name: name.to_owned(),
+            transform: Matrix4::identity(),
+            material: None,
+            visible: true,
+
+            object: None,
+            parent,
+            children: vec![],
+        }));
+
+        id
+    }
+
+    /// # Panics
+    ///
+    /// If the node was removed.
+    pub fn node(&self, id: NodeId) -> &SceneNode {
+        self.nodes[id.0].as_ref().expect("scene node was removed")
+    }
+
+    /// # Panics
+    ///
+    /// If the node was removed.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut SceneNode {
+        self.nodes[id.0].as_mut().expect("scene node was removed")
+    }
+
+    /// The first node added or renamed to `name` that was not removed or renamed since.
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.names.get(name)?.first().copied()
+    }
+
+    /// # Panics
+    ///
+    /// If the node was removed.
+    pub fn rename(&mut self, id: NodeId, name: &str) {
+        let old = std::mem::replace(&mut self.node_mut(id).name, name.to_owned());
+        self.forget_name(&old, id);
+        self.names.entry(name.to_owned()).or_default().push(id);
+    }
+
+    #[inline]
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    /// Moves a node with its descendants under `parent`, or to the roots. Its transform stays
+    /// relative to the parent, so the node moves along with it.
+    ///
+    /// # Panics
+    ///
+    /// If either node was removed, or `parent` is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, id: NodeId, parent: Option<NodeId>) {
+        let mut ancestor = parent;
+        while let Some(current) = ancestor {
+            assert!(current != id, "scene node cannot descend from itself");
+            ancestor = self.node(current).parent;
+        }
+
+        let old_parent = self.node(id).parent;
+        self.siblings(old_parent).retain(|&sibling| sibling != id);
+        self.siblings(parent).push(id);
+        self.node_mut(id).parent = parent;
+    }
+
+    /// Removes a node with its descendants.
+    ///
+    /// # Panics
+    ///
+    /// If the node was removed before.
+    pub fn remove(&mut self, id: NodeId) {
+        let parent = self.node(id).parent;
+        self.siblings(parent).retain(|&sibling| sibling != id);
+
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                self.forget_name(&node.name, id);
+                removed.extend(node.children);
+            }
+        }
+    }
+
+    /// Transform from the frame of the node to the world frame.
+    pub fn world_transform(&self, id: NodeId) -> Matrix4<f64> {
+        let node = self.node(id);
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * node.transform,
+            None => node.transform,
+        }
+    }
+
+    /// Builds the visible nodes into a hierarchy of BVHs and transforms to render.
+    ///
+    /// The objects are shared rather than copied, so building again after an edit is cheap. Nodes
+    /// with a singular transform, e.g. scaled to nothing, or a projective one are left out with
+    /// their descendants like hidden nodes.
+    pub fn flatten(&self) -> Bvh {
+        let mut world = HittableList::new();
+        for &root in &self.roots {
+            if let Some(object) = self.flatten_node(root, None) {
+                world.push_shared(object);
+            }
+        }
+
+        Bvh::from_list(&mut world)
+    }
+
+    fn flatten_node(
+        &self,
+        id: NodeId,
+        inherited: Option<&Material>,
+    ) -> Option<Arc<dyn Hittable + Send + Sync>> {
+        let node = self.node(id);
+        if !node.visible
+            || is_singular(linear_part(node.transform))
+            || is_projective(node.transform)
+        {
+            return None;
+        }
+
+        let material = node.material.as_ref().or(inherited);
+        let mut objects = HittableList::new();
+        if let Some(object) = &node.object {
+            // Overriding only the node's own object lets descendants choose their own material.
+            objects.push_shared(match material {
+                Some(material) => MaterialOverride::new_shared(object.clone(), material.clone()),
+                None => object.clone(),
+            });
+        }
+        for &child in &node.children {
+            if let Some(child) = self.flatten_node(child, material) {
+                objects.push_shared(child);
+            }
+        }
+
+        let object = objects.into_shared()?;
+        if node.transform == Matrix4::identity() {
+            return Some(object);
+        }
+
+        Some(Transform::from_matrix_shared(object, node.transform)?)
+    }
+
+    fn forget_name(&mut self, name: &str, id: NodeId) {
+        if let Some(ids) = self.names.get_mut(name) {
+            ids.retain(|&other| other != id);
+            if ids.is_empty() {
+                self.names.remove(name);
+            }
+        }
+    }
+
+    fn siblings(&mut self, parent: Option<NodeId>) -> &mut Vec<NodeId> {
+        match parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{hittable::Sphere, ray::Ray, Color};
+    use cgmath::{Point3, Vector3};
+
+    fn sphere() -> Arc<Sphere> {
+        let material = Material::lambertian(Color::new(0.5, 0.5, 0.5).into());
+        Sphere::new(Point3::origin(), 1., material)
+    }
+
+    #[test]
+    fn finds_first_remaining_node_by_name() {
+        let mut scene = SceneGraph::new();
+        let first = scene.add("lamp", None);
+        let group = scene.add("group", None);
+        let second = scene.add("lamp", Some(group));
+        assert_eq!(scene.find("lamp"), Some(first));
+
+        scene.remove(first);
+        assert_eq!(scene.find("lamp"), Some(second));
+        scene.rename(second, "light");
+        assert_eq!(scene.find("lamp"), None);
+        assert_eq!(scene.find("light"), Some(second));
+        assert_eq!(scene.node(second).name(), "light");
+
+        scene.remove(group);
+        assert_eq!(scene.find("light"), None);
+    }
+
+    #[test]
+    fn flatten_skips_nodes_scaled_to_nothing() {
+        let mut scene = SceneGraph::new();
+        let flat = scene.add("flat", None);
+        scene
+            .node_mut(flat)
+            .transform(Matrix4::from_nonuniform_scale(1., 0., 1.));
+        let child = scene.add("child", Some(flat));
+        scene.node_mut(child).object(sphere());
+        let kept = scene.add("kept", None);
+        scene
+            .node_mut(kept)
+            .object(sphere())
+            .transform(Matrix4::from_translation(Vector3::new(5., 0., 0.)));
+
+        let world = scene.flatten();
+        let ray = |x| Ray {
+            origin: Point3::new(x, 0., 5.),
+            direction: Vector3::new(0., 0., -1.),
+            time: 0.,
+        };
+        assert!(world.hit(&ray(0.), 0.001..f64::INFINITY).is_none());
+        assert!(world.hit(&ray(5.), 0.001..f64::INFINITY).is_some());
+    }
+}
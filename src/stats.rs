@@ -0,0 +1,244 @@
+use crate::distributed::wire::{Decode, Decoder, Encode, Encoder};
+use std::{
+    cell::Cell,
+    fmt, io,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Event counted while rendering.
+#[derive(Clone, Copy)]
+pub(crate) enum Counter {
+    CameraRays,
+    SecondaryRays,
+    BvhNodes,
+    SphereTests,
+    QuadTests,
+    TriangleTests,
+    ShapeTests,
+    MediumTests,
+}
+
+const COUNTERS: usize = 8;
+
+// Counting into a thread-local first keeps the hot loops free of contended atomics. The counts
+// only ever grow, and a `Tally` takes the difference over the work it records.
+thread_local! {
+    static LOCAL: [Cell<u64>; COUNTERS] = Default::default();
+}
+
+#[inline]
+pub(crate) fn count(counter: Counter) {
+    LOCAL.with(|local| {
+        let cell = &local[counter as usize];
+        cell.set(cell.get() + 1);
+    });
+}
+
+fn local_counts() -> [u64; COUNTERS] {
+    LOCAL.with(|local| local.each_ref().map(Cell::get))
+}
+
+/// Events counted by the work of a single render, on whichever threads it runs.
+pub(crate) struct Tally([AtomicU64; COUNTERS]);
+
+impl Tally {
+    pub(crate) fn new() -> Self {
+        Self([const { AtomicU64::new(0) }; COUNTERS])
+    }
+
+    /// Runs `work` on the current thread and adds the events it counted.
+    pub(crate) fn record<T>(&self, work: impl FnOnce() -> T) -> T {
+        let before = local_counts();
+        let result = work();
+        let after = local_counts();
+
+        for ((total, after), before) in self.0.iter().zip(after).zip(before) {
+            total.fetch_add(after.wrapping_sub(before), Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    /// Statistics of the work recorded so far, which took `render` after a BVH build of
+    /// `bvh_build`.
+    pub(crate) fn stats(&self, bvh_build: Duration, render: Duration) -> RenderStats {
+        let counts = self.0.each_ref().map(|total| total.load(Ordering::Relaxed));
+
+        RenderStats {
+            camera_rays: counts[Counter::CameraRays as usize],
+            secondary_rays: counts[Counter::SecondaryRays as usize],
+            bvh_nodes_visited: counts[Counter::BvhNodes as usize],
+            sphere_tests: counts[Counter::SphereTests as usize],
+            quad_tests: counts[Counter::QuadTests as usize],
+            triangle_tests: counts[Counter::TriangleTests as usize],
+            shape_tests: counts[Counter::ShapeTests as usize],
+            medium_tests: counts[Counter::MediumTests as usize],
+            bvh_build,
+            render,
+            write: Duration::ZERO,
+            cancelled: false,
+        }
+    }
+}
+
+/// Work done by a render and the time it took.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    /// Rays scattered off surfaces and media.
+    pub secondary_rays: u64,
+    pub bvh_nodes_visited: u64,
+    pub sphere_tests: u64,
+    pub quad_tests: u64,
+    pub triangle_tests: u64,
+    /// Tests against disks, cylinders, cones and tori.
+    pub shape_tests: u64,
+    pub medium_tests: u64,
+
+    /// Time spent building the BVHs of the rendered scene.
+    pub bvh_build: Duration,
+    pub render: Duration,
+    pub write: Duration,
+
+    /// Whether the render was cancelled before it was complete.
+    pub cancelled: bool,
+}
+
+impl RenderStats {
+    pub fn rays(&self) -> u64 {
+        self.camera_rays + self.secondary_rays
+    }
+
+    /// Mean number of rays traced per camera ray, i.e. per path.
+    pub fn average_path_length(&self) -> f64 {
+        if self.camera_rays == 0 {
+            return 0.;
+        }
+
+        (self.camera_rays + self.secondary_rays) as f64 / self.camera_rays as f64
+    }
+
+    pub fn rays_per_second(&self) -> f64 {
+        if self.render.is_zero() {
+            return 0.;
+        }
+
+        self.rays() as f64 / self.render.as_secs_f64()
+    }
+
+    /// Adds the work of `other`, e.g. to total the frames of a sequence.
+    pub fn merge(&mut self, other: &RenderStats) {
+        self.camera_rays += other.camera_rays;
+        self.secondary_rays += other.secondary_rays;
+        self.bvh_nodes_visited += other.bvh_nodes_visited;
+        self.sphere_tests += other.sphere_tests;
+        self.quad_tests += other.quad_tests;
+        self.triangle_tests += other.triangle_tests;
+        self.shape_tests += other.shape_tests;
+        self.medium_tests += other.medium_tests;
+
+        self.bvh_build += other.bvh_build;
+        self.render += other.render;
+        self.write += other.write;
+
+        self.cancelled |= other.cancelled;
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.cancelled {
+            writeln!(f, "Cancelled")?;
+        }
+        writeln!(f, "Rays")?;
+        writeln!(f, "  camera              {:>14}", self.camera_rays)?;
+        writeln!(f, "  secondary           {:>14}", self.secondary_rays)?;
+        writeln!(
+            f,
+            "  average path length {:>14.2}",
+            self.average_path_length()
+        )?;
+        writeln!(f, "  per second          {:>14.0}", self.rays_per_second())?;
+        writeln!(f, "Intersections")?;
+        writeln!(f, "  BVH nodes visited   {:>14}", self.bvh_nodes_visited)?;
+        writeln!(f, "  sphere tests        {:>14}", self.sphere_tests)?;
+        writeln!(f, "  quad tests          {:>14}", self.quad_tests)?;
+        writeln!(f, "  triangle tests      {:>14}", self.triangle_tests)?;
+        writeln!(f, "  shape tests         {:>14}", self.shape_tests)?;
+        writeln!(f, "  medium tests        {:>14}", self.medium_tests)?;
+        writeln!(f, "Time")?;
+        writeln!(f, "  BVH build           {:>14.3?}", self.bvh_build)?;
+        writeln!(f, "  render              {:>14.3?}", self.render)?;
+        write!(f, "  write               {:>14.3?}", self.write)
+    }
+}
+
+impl Encode for RenderStats {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder
+            .put(&self.camera_rays)
+            .put(&self.secondary_rays)
+            .put(&self.bvh_nodes_visited)
+            .put(&self.sphere_tests)
+            .put(&self.quad_tests)
+            .put(&self.triangle_tests)
+            .put(&self.shape_tests)
+            .put(&self.medium_tests)
+            .put(&self.bvh_build)
+            .put(&self.render)
+            .put(&self.write)
+            .put(&self.cancelled);
+    }
+}
+
+impl Decode for RenderStats {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            camera_rays: decoder.get()?,
+            secondary_rays: decoder.get()?,
+            bvh_nodes_visited: decoder.get()?,
+            sphere_tests: decoder.get()?,
+            quad_tests: decoder.get()?,
+            triangle_tests: decoder.get()?,
+            shape_tests: decoder.get()?,
+            medium_tests: decoder.get()?,
+            bvh_build: decoder.get()?,
+            render: decoder.get()?,
+            write: decoder.get()?,
+            cancelled: decoder.get()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tally_records_only_its_own_work() {
+        let (first, second) = (Tally::new(), Tally::new());
+        count(Counter::SphereTests);
+        first.record(|| {
+            count(Counter::CameraRays);
+            second.record(|| count(Counter::QuadTests));
+        });
+
+        let first = first.stats(Duration::ZERO, Duration::ZERO);
+        let second = second.stats(Duration::ZERO, Duration::ZERO);
+        assert_eq!(
+            (first.camera_rays, first.quad_tests, first.sphere_tests),
+            (1, 1, 0)
+        );
+        assert_eq!((second.camera_rays, second.quad_tests), (0, 1));
+    }
+
+    #[test]
+    fn instant_render_has_no_ray_rate() {
+        let stats = RenderStats {
+            camera_rays: 10,
+            ..Default::default()
+        };
+        assert_eq!(stats.rays_per_second(), 0.);
+    }
+}
@@ -1,10 +1,16 @@
-use super::Texture;
-use crate::{lerp, rgb_to_color, Color};
-use cgmath::Point3;
-use image::{DynamicImage, GenericImageView, Pixel};
+use super::{tag, Texture};
+use crate::{
+    distributed::wire::{invalid_data, Decode, Decoder, EncodeObject, Encoder},
+    lerp, rgb_to_color, Color,
+};
+use cgmath::{prelude::*, Point3};
+use image::{DynamicImage, GenericImageView, Pixel, RgbImage};
+use std::{io, sync::Arc};
 
+/// Texture of an sRGB-encoded image, multiplied by a linear sRGB tint.
 pub struct ImageTexture {
-    image: DynamicImage,
+    image: Arc<DynamicImage>,
+    tint: Color,
 }
 
 impl Texture for ImageTexture {
@@ -25,18 +31,32 @@ impl Texture for ImageTexture {
         let i2 = (i1 + 1).min(self.image.width() - 1);
         let j2 = (j1 + 1).min(self.image.height() - 1);
 
-        let c00 = rgb_to_color(self.image.get_pixel(i1, j1).to_rgb().0);
-        let c10 = rgb_to_color(self.image.get_pixel(i2, j1).to_rgb().0);
-        let c01 = rgb_to_color(self.image.get_pixel(i1, j2).to_rgb().0);
-        let c11 = rgb_to_color(self.image.get_pixel(i2, j2).to_rgb().0);
+        let texel = |i, j| rgb_to_color(self.image.get_pixel(i, j).to_rgb().0);
+        let c00 = texel(i1, j1);
+        let c10 = texel(i2, j1);
+        let c01 = texel(i1, j2);
+        let c11 = texel(i2, j2);
 
-        let t = x - a as f64;
-        let s = y - b as f64;
+        let t = x - a;
+        let s = y - b;
 
         let c0 = lerp(c00, c10, t);
         let c1 = lerp(c01, c11, t);
 
-        lerp(c0, c1, s)
+        lerp(c0, c1, s).mul_element_wise(self.tint)
+    }
+}
+
+impl EncodeObject for ImageTexture {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        // Texels are only ever read as 8-bit RGB, so nothing is lost by sending them as such.
+        let image = self.image.to_rgb8();
+        encoder
+            .put(&tag::IMAGE)
+            .put(&image.width())
+            .put(&image.height())
+            .bytes(image.as_raw())
+            .put(&self.tint);
     }
 }
 
@@ -46,6 +66,28 @@ impl ImageTexture {
 
         let image = ImageReader::open(path)?.decode().unwrap();
 
-        Ok(Self { image })
+        Ok(Self::from_image(Arc::new(image), Color::new(1., 1., 1.)))
+    }
+
+    /// Texture of an already decoded image, which may be shared with other textures.
+    pub(crate) fn from_image(image: Arc<DynamicImage>, tint: Color) -> Self {
+        Self { image, tint }
+    }
+}
+
+impl Decode for ImageTexture {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let width: u32 = decoder.get()?;
+        let height: u32 = decoder.get()?;
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|len| len.checked_mul(3))
+            .ok_or_else(|| invalid_data("image texture too large"))?;
+        let image = RgbImage::from_raw(width, height, decoder.bytes(len)?.to_vec()).unwrap();
+
+        Ok(Self {
+            image: Arc::new(DynamicImage::ImageRgb8(image)),
+            tint: decoder.get()?,
+        })
     }
 }
@@ -1,13 +1,52 @@
-use crate::Color;
+use crate::{
+    distributed::wire::{unknown_tag, Decode, Decoder, Encode, EncodeObject, Encoder},
+    hittable::HitPayload,
+    Color,
+};
 
 mod image_texture;
 mod perlin;
+mod vertex_color;
 
 use cgmath::Point3;
+use std::{io, sync::Arc};
 
 pub use image_texture::ImageTexture;
 pub use perlin::PerlinTexture;
+pub use vertex_color::VertexColorTexture;
 
-pub(crate) trait Texture {
+pub(crate) trait Texture: EncodeObject {
     fn value(&self, u: f64, v: f64, p: &Point3<f64>) -> Color;
+
+    /// Color at a hit, for textures depending on more than its surface coordinates and point.
+    fn value_at(&self, payload: &HitPayload) -> Color {
+        self.value(payload.u, payload.v, &payload.point)
+    }
+}
+
+pub(crate) mod tag {
+    pub(crate) const COLOR: u8 = 0;
+    pub(crate) const IMAGE: u8 = 1;
+    pub(crate) const PERLIN: u8 = 2;
+    pub(crate) const VERTEX_COLOR: u8 = 3;
+}
+
+impl Encode for dyn Texture + Send + Sync {
+    fn encode(&self, encoder: &mut Encoder) {
+        self.encode_object(encoder);
+    }
+}
+
+impl Decode for Arc<dyn Texture + Send + Sync> {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        decoder.shared(|decoder| -> io::Result<Self> {
+            Ok(match decoder.get()? {
+                tag::COLOR => Arc::new(decoder.get::<Color>()?),
+                tag::IMAGE => Arc::new(decoder.get::<ImageTexture>()?),
+                tag::PERLIN => Arc::new(decoder.get::<PerlinTexture>()?),
+                tag::VERTEX_COLOR => Arc::new(decoder.get::<VertexColorTexture>()?),
+                tag => return Err(unknown_tag("texture", tag)),
+            })
+        })
+    }
 }
@@ -1,7 +1,11 @@
-use super::Texture;
-use crate::{lerp, Color};
+use super::{tag, Texture};
+use crate::{
+    distributed::wire::{invalid_data, Decode, Decoder, Encode, EncodeObject, Encoder},
+    lerp, Color,
+};
 use cgmath::{prelude::*, Point3, Vector3};
 use rand::prelude::*;
+use std::io;
 
 struct Perlin {
     ranvec: Box<[Vector3<f64>; Self::POINT_COUNT]>,
@@ -96,6 +100,37 @@ impl Perlin {
     }
 }
 
+// The tables are random, so they are sent along for every process to see the same noise.
+impl Encode for Perlin {
+    fn encode(&self, encoder: &mut Encoder) {
+        encoder.put(self.ranvec.as_ref());
+        for perm in [&self.perm_x, &self.perm_y, &self.perm_z] {
+            encoder.put(perm.as_ref());
+        }
+    }
+}
+
+impl Decode for Perlin {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        let ranvec = Box::new(decoder.get()?);
+        let mut perm = || -> io::Result<Box<[usize; Self::POINT_COUNT]>> {
+            let perm: [usize; Self::POINT_COUNT] = decoder.get()?;
+            if perm.iter().any(|&i| i >= Self::POINT_COUNT) {
+                return Err(invalid_data("perlin permutation out of range"));
+            }
+
+            Ok(Box::new(perm))
+        };
+
+        Ok(Self {
+            ranvec,
+            perm_x: perm()?,
+            perm_y: perm()?,
+            perm_z: perm()?,
+        })
+    }
+}
+
 pub struct PerlinTexture {
     perlin: Perlin,
     scale: f64,
@@ -108,6 +143,12 @@ impl Texture for PerlinTexture {
     }
 }
 
+impl EncodeObject for PerlinTexture {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder.put(&tag::PERLIN).put(&self.perlin).put(&self.scale);
+    }
+}
+
 impl PerlinTexture {
     pub fn new(scale: f64) -> Self {
         Self {
@@ -116,3 +157,12 @@ impl PerlinTexture {
         }
     }
 }
+
+impl Decode for PerlinTexture {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            perlin: decoder.get()?,
+            scale: decoder.get()?,
+        })
+    }
+}
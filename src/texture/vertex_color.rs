@@ -0,0 +1,44 @@
+use super::{tag, Texture};
+use crate::{
+    distributed::wire::{Decode, Decoder, EncodeObject, Encoder},
+    hittable::HitPayload,
+    Color,
+};
+use cgmath::Point3;
+use std::io;
+
+/// Colors of the vertices of a mesh interpolated across its faces, for meshes loaded with colors
+/// such as scans. Surfaces without vertex colors get the fallback color. Both are linear sRGB.
+pub struct VertexColorTexture {
+    fallback: Color,
+}
+
+impl Texture for VertexColorTexture {
+    fn value(&self, _u: f64, _v: f64, _p: &Point3<f64>) -> Color {
+        self.fallback
+    }
+
+    fn value_at(&self, payload: &HitPayload) -> Color {
+        payload.vertex_color.unwrap_or(self.fallback)
+    }
+}
+
+impl EncodeObject for VertexColorTexture {
+    fn encode_object(&self, encoder: &mut Encoder) {
+        encoder.put(&tag::VERTEX_COLOR).put(&self.fallback);
+    }
+}
+
+impl VertexColorTexture {
+    pub fn new(fallback: Color) -> Self {
+        Self { fallback }
+    }
+}
+
+impl Decode for VertexColorTexture {
+    fn decode(decoder: &mut Decoder) -> io::Result<Self> {
+        Ok(Self {
+            fallback: decoder.get()?,
+        })
+    }
+}
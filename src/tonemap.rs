@@ -0,0 +1,166 @@
+use crate::{colorspace::mul, luminance, Color};
+use cgmath::prelude::*;
+
+/// Operator compressing scene radiance into the displayable range.
+#[derive(Clone, Copy)]
+pub enum ToneMapper {
+    /// Hard clip at 1.
+    Clamp,
+    Reinhard,
+    /// Reinhard operator mapping the luminance `white` to 1.
+    ExtendedReinhard {
+        white: f64,
+    },
+    /// Fit of the ACES reference rendering and output transforms.
+    AcesFilmic,
+    AgX,
+}
+
+/// Conversion of the linear radiance of the film into linear display values in `[0, 1]`.
+#[derive(Clone, Copy)]
+pub struct ToneMapping {
+    /// Exposure adjustment in stops.
+    pub exposure: f64,
+    /// Per-channel gains, see [`white_balance`].
+    pub white_balance: Color,
+    pub tone_mapper: ToneMapper,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            exposure: 0.,
+            white_balance: Color::from([1.; 3]),
+            tone_mapper: ToneMapper::Clamp,
+        }
+    }
+}
+
+impl ToneMapping {
+    /// Applies only exposure and white balance, keeping the result scene-linear.
+    pub fn expose(&self, color: Color) -> Color {
+        2f64.powf(self.exposure) * color.mul_element_wise(self.white_balance)
+    }
+
+    pub fn apply(&self, color: Color) -> Color {
+        let color = self.expose(color).map(|c| c.max(0.));
+
+        let mapped = match self.tone_mapper {
+            ToneMapper::Clamp => color,
+            ToneMapper::Reinhard => reinhard(color, f64::INFINITY),
+            ToneMapper::ExtendedReinhard { white } => reinhard(color, white),
+            ToneMapper::AcesFilmic => aces_filmic(color),
+            ToneMapper::AgX => agx(color),
+        };
+
+        mapped.map(|c| c.clamp(0., 1.))
+    }
+}
+
+/// Gains that make a white surface lit by a black body of `temperature` Kelvin appear neutral.
+pub fn white_balance(temperature: f64) -> Color {
+    let reference = black_body_rgb(6500.);
+    let gains = reference.div_element_wise(black_body_rgb(temperature));
+
+    gains / gains.y
+}
+
+/// Linear sRGB color of a black body, from the approximation of the Planckian locus by Kim et al.
+fn black_body_rgb(temperature: f64) -> Color {
+    let t = temperature.clamp(1667., 25000.);
+    let (t2, t3) = (t * t, t * t * t);
+
+    let x = if t <= 4000. {
+        -0.2661239e9 / t3 - 0.2343589e6 / t2 + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / t3 + 2.1070379e6 / t2 + 0.2226347e3 / t + 0.240390
+    };
+    let (x2, x3) = (x * x, x * x * x);
+
+    let y = if t <= 2222. {
+        -1.1063814 * x3 - 1.34811020 * x2 + 2.18555832 * x - 0.20219683
+    } else if t <= 4000. {
+        -0.9549476 * x3 - 1.37418593 * x2 + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x3 - 5.87338670 * x2 + 3.75112997 * x - 0.37001483
+    };
+
+    let xyz = Color::new(x / y, 1., (1. - x - y) / y);
+    mul(
+        [
+            [3.2404542, -1.5371385, -0.4985314],
+            [-0.9692660, 1.8760108, 0.0415560],
+            [0.0556434, -0.2040259, 1.0572252],
+        ],
+        xyz,
+    )
+}
+
+fn reinhard(color: Color, white: f64) -> Color {
+    let l = luminance(color);
+    if l <= 0. {
+        return color;
+    }
+
+    let mapped = l * (1. + l / (white * white)) / (1. + l);
+    color * (mapped / l)
+}
+
+/// Stephen Hill's fit of the ACES RRT and sRGB ODT.
+fn aces_filmic(color: Color) -> Color {
+    let color = mul(
+        [
+            [0.59719, 0.35458, 0.04823],
+            [0.07600, 0.90834, 0.01566],
+            [0.02840, 0.13383, 0.83777],
+        ],
+        color,
+    );
+
+    let color = color
+        .map(|v| (v * (v + 0.0245786) - 0.000090537) / (v * (0.983729 * v + 0.4329510) + 0.238081));
+
+    mul(
+        [
+            [1.60475, -0.53108, -0.07367],
+            [-0.10208, 1.10813, -0.00605],
+            [-0.00327, -0.07276, 1.07602],
+        ],
+        color,
+    )
+}
+
+/// Minimal AgX with a polynomial approximation of the default contrast curve.
+fn agx(color: Color) -> Color {
+    const MIN_EV: f64 = -12.47393;
+    const MAX_EV: f64 = 4.026069;
+
+    let color = mul(
+        [
+            [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+            [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+            [0.0423756549057051, 0.0784336, 0.879142973793104],
+        ],
+        color,
+    );
+
+    let color = color.map(|v| {
+        let x = (v.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+        let x2 = x * x;
+        let x4 = x2 * x2;
+
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x
+            - 0.00232
+    });
+
+    let color = mul(
+        [
+            [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+            [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+            [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+        ],
+        color,
+    );
+
+    color.map(|v| v.max(0.).powf(2.2))
+}